@@ -0,0 +1,322 @@
+//! Backfill of the events of a block range.
+//!
+//! The range is split into chunks of blocks, fetched in parallel by workers
+//! under a concurrency limit. Chunks are handed to the caller in block order,
+//! and a checkpoint is written after each of them so an interrupted backfill
+//! resumes after the last chunk handed. As the end of the range is usually the
+//! latest block, resolved again on each run, only the start of the range must
+//! match the checkpoint: a resumed backfill goes up to its new end.
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{BlockId, EmittedEvent, FieldElement};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+use crate::starknet_utils::client::{StarknetClient, StarknetClientError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackfillError {
+    #[error("Client error: {0}")]
+    Client(#[from] StarknetClientError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid checkpoint: {0}")]
+    Checkpoint(#[from] serde_json::Error),
+    #[error("Invalid block range: {0}")]
+    InvalidRange(String),
+    #[error("Worker failed: {0}")]
+    Worker(String),
+}
+
+/// Inclusive range of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    pub range: BlockRange,
+    /// Number of blocks fetched by a worker at once.
+    pub chunk_size: u64,
+    /// Maximum number of chunks fetched at the same time.
+    pub concurrency: usize,
+    pub keys: Option<Vec<Vec<FieldElement>>>,
+    pub contract_address: Option<FieldElement>,
+    /// File where the progress is saved, the backfill resumes from it if it exists
+    /// and starts at the same block.
+    pub checkpoint_path: Option<PathBuf>,
+}
+
+impl BackfillConfig {
+    pub fn new(range: BlockRange) -> Self {
+        Self {
+            range,
+            chunk_size: 1000,
+            concurrency: 4,
+            keys: None,
+            contract_address: None,
+            checkpoint_path: None,
+        }
+    }
+}
+
+/// Events of a chunk of blocks.
+#[derive(Debug, Clone)]
+pub struct ChunkEvents {
+    pub range: BlockRange,
+    /// Events by block number, in the order they were emitted.
+    pub events: BTreeMap<u64, Vec<EmittedEvent>>,
+}
+
+/// Progress of a backfill, written after each chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub range: BlockRange,
+    /// First block not handed to the caller yet.
+    pub next_block: u64,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint, `None` if the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>, BackfillError> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the checkpoint, replacing the file atomically.
+    pub fn save(&self, path: &Path) -> Result<(), BackfillError> {
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Fetches the events of the configured range and hands them to `on_chunk`,
+/// one chunk at a time and in block order.
+/// Returns the number of events fetched.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client shared by the workers.
+/// * `config` - The backfill configuration.
+/// * `on_chunk` - Called with each chunk, the checkpoint is written after it returns.
+pub async fn backfill<C, F>(
+    client: Arc<C>,
+    config: BackfillConfig,
+    mut on_chunk: F,
+) -> Result<usize, BackfillError>
+where
+    C: StarknetClient + Send + Sync + 'static,
+    F: FnMut(&ChunkEvents) -> Result<(), BackfillError>,
+{
+    let BlockRange { from, to } = config.range;
+    if from > to || config.chunk_size == 0 || config.concurrency == 0 {
+        return Err(BackfillError::InvalidRange(format!(
+            "from {} to {} by chunks of {} with {} workers",
+            from, to, config.chunk_size, config.concurrency
+        )));
+    }
+
+    let mut start = from;
+    if let Some(path) = &config.checkpoint_path {
+        if let Some(checkpoint) = Checkpoint::load(path)? {
+            if checkpoint.range.from != from {
+                return Err(BackfillError::InvalidRange(format!(
+                    "checkpoint {} is for blocks {} to {}",
+                    path.display(),
+                    checkpoint.range.from,
+                    checkpoint.range.to
+                )));
+            }
+            start = checkpoint.next_block;
+        }
+    }
+
+    let chunks: Vec<BlockRange> = (start..=to)
+        .step_by(config.chunk_size as usize)
+        .map(|from| BlockRange {
+            from,
+            to: to.min(from + config.chunk_size - 1),
+        })
+        .collect();
+
+    // Chunks are only fetched ahead of the next one to hand within this
+    // window, to bound the memory used by chunks waiting for a slower one.
+    let window = config.concurrency * 2;
+
+    let mut workers = JoinSet::new();
+    let mut fetched: BTreeMap<usize, ChunkEvents> = BTreeMap::new();
+    let mut next_spawn = 0;
+    let mut next_handed = 0;
+    let mut total_events = 0;
+
+    while next_handed < chunks.len() {
+        while next_spawn < chunks.len()
+            && workers.len() < config.concurrency
+            && next_spawn < next_handed + window
+        {
+            let client = Arc::clone(&client);
+            let range = chunks[next_spawn];
+            let keys = config.keys.clone();
+            let contract_address = config.contract_address;
+            let index = next_spawn;
+
+            workers.spawn(async move {
+                fetch_chunk(client.as_ref(), range, keys, contract_address)
+                    .await
+                    .map(|chunk| (index, chunk))
+            });
+
+            next_spawn += 1;
+        }
+
+        let (index, chunk) = match workers.join_next().await {
+            Some(result) => result.map_err(|e| BackfillError::Worker(e.to_string()))??,
+            None => break,
+        };
+        fetched.insert(index, chunk);
+
+        while let Some(chunk) = fetched.remove(&next_handed) {
+            on_chunk(&chunk)?;
+            total_events += chunk.events.values().map(Vec::len).sum::<usize>();
+
+            if let Some(path) = &config.checkpoint_path {
+                Checkpoint {
+                    range: config.range,
+                    next_block: chunk.range.to + 1,
+                }
+                .save(path)?;
+            }
+
+            next_handed += 1;
+        }
+    }
+
+    Ok(total_events)
+}
+
+/// Fetches all the events of a chunk, following the continuation tokens.
+async fn fetch_chunk<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    range: BlockRange,
+    keys: Option<Vec<Vec<FieldElement>>>,
+    contract_address: Option<FieldElement>,
+) -> Result<ChunkEvents, BackfillError> {
+    let mut events: BTreeMap<u64, Vec<EmittedEvent>> = BTreeMap::new();
+    let mut continuation_token = None;
+
+    loop {
+        let page = client
+            .fetch_events(
+                Some(BlockId::Number(range.from)),
+                Some(BlockId::Number(range.to)),
+                keys.clone(),
+                contract_address,
+                continuation_token,
+            )
+            .await?;
+
+        for (block_number, block_events) in page.events {
+            events.entry(block_number).or_default().extend(block_events);
+        }
+
+        continuation_token = page.continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(ChunkEvents { range, events })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use crate::starknet_utils::EventResult;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Client returning no events, recording the chunks fetched.
+    fn client(fetched: Arc<Mutex<Vec<BlockRange>>>) -> Arc<MockStarknetClient> {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_fetch_events()
+            .returning(move |from, to, _, _, _| {
+                let block = |id| match id {
+                    Some(BlockId::Number(n)) => n,
+                    _ => panic!("chunks are fetched by block number"),
+                };
+                fetched.lock().unwrap().push(BlockRange {
+                    from: block(from),
+                    to: block(to),
+                });
+                Ok(EventResult {
+                    events: HashMap::new(),
+                    continuation_token: None,
+                })
+            });
+        Arc::new(client)
+    }
+
+    fn config(to: u64, checkpoint_path: &Path) -> BackfillConfig {
+        let mut config = BackfillConfig::new(BlockRange { from: 0, to });
+        config.chunk_size = 5;
+        config.concurrency = 1;
+        config.checkpoint_path = Some(checkpoint_path.to_path_buf());
+        config
+    }
+
+    #[tokio::test]
+    async fn resume_up_to_the_new_latest_block() {
+        let path =
+            std::env::temp_dir().join(format!("backfill-resume-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let fetched = Arc::new(Mutex::new(vec![]));
+
+        // Interrupted while handing the second chunk.
+        let mut handed = 0;
+        let result = backfill(client(fetched.clone()), config(9, &path), |_| {
+            handed += 1;
+            match handed {
+                1 => Ok(()),
+                _ => Err(BackfillError::Worker("interrupted".to_string())),
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(Checkpoint::load(&path).unwrap().unwrap().next_block, 5);
+
+        // The latest block moved while the backfill was stopped.
+        fetched.lock().unwrap().clear();
+        backfill(client(fetched.clone()), config(12, &path), |_| Ok(()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *fetched.lock().unwrap(),
+            vec![
+                BlockRange { from: 5, to: 9 },
+                BlockRange { from: 10, to: 12 }
+            ]
+        );
+        let checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(checkpoint.range, BlockRange { from: 0, to: 12 });
+        assert_eq!(checkpoint.next_block, 13);
+
+        // Another start is not resumed from this checkpoint.
+        let mut other = config(12, &path);
+        other.range.from = 1;
+        let result = backfill(client(fetched), other, |_| Ok(())).await;
+        assert!(matches!(result, Err(BackfillError::InvalidRange(_))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
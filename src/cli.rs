@@ -0,0 +1,158 @@
+//! Command line interface of the binary.
+pub mod output;
+
+use block_issue_sample::config::{parse_felt, parse_selector};
+use clap::{Args, Parser, Subcommand};
+use starknet::core::types::FieldElement;
+use std::path::PathBuf;
+
+use output::OutputFormat;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Starknet NFT indexing toolbox")]
+pub struct Cli {
+    /// Configuration file, in TOML.
+    #[arg(long, global = true, env = "INDEXER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// RPC url of the Starknet node, overrides the url of the network.
+    #[arg(long, global = true)]
+    pub rpc_url: Option<String>,
+
+    /// Network to connect to, when no RPC url is given.
+    #[arg(long, global = true, value_parser = ["mainnet", "sepolia"])]
+    pub network: Option<String>,
+
+    /// Output format of the results.
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Log level filter, like `info` or `info,block_issue_sample=debug`.
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// Format of the logs written to stderr.
+    #[arg(long, global = true, value_parser = ["pretty", "json"])]
+    pub log_format: Option<String>,
+
+    /// Serves the Prometheus metrics on this address, like `127.0.0.1:9898`.
+    #[arg(long, global = true)]
+    pub metrics_address: Option<String>,
+
+    /// Serves `/healthz` and `/readyz` on this address, like `127.0.0.1:9899`,
+    /// while watching or backfilling.
+    #[arg(long, global = true)]
+    pub health_address: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Follows the pending block and the new latest blocks.
+    Watch,
+    /// Fetches the events of a block range, resuming from the checkpoint if any.
+    Backfill(BackfillArgs),
+    /// Queries the events of a block range, optionally of a contract.
+    Events(EventsArgs),
+    /// Calls a contract entrypoint.
+    Call(CallArgs),
+    /// Shows the information of a block.
+    Block {
+        /// Block number, hash, `latest` or `pending`.
+        #[arg(default_value = "latest")]
+        id: String,
+    },
+    /// Decodes Cairo values from felts.
+    Decode {
+        #[command(subcommand)]
+        value: DecodeCommand,
+    },
+    /// Manages the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Prints the configuration resulting from all the layers.
+    Print,
+}
+
+#[derive(Debug, Args)]
+pub struct BackfillArgs {
+    /// First block of the range.
+    #[arg(long)]
+    pub from: String,
+    /// Last block of the range, included.
+    #[arg(long, default_value = "latest")]
+    pub to: String,
+    /// Number of blocks fetched by a worker at once.
+    #[arg(long)]
+    pub chunk_size: Option<u64>,
+    /// Maximum number of chunks fetched at the same time.
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+    /// File where the progress is saved. A backfill starting at the same
+    /// block resumes from it, up to the new `--to`.
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+    /// File the events are appended to, one per line in the output format,
+    /// the sink file or stdout if not given.
+    #[arg(long)]
+    pub out_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct EventsArgs {
+    /// First block of the range.
+    #[arg(long, default_value = "latest")]
+    pub from: String,
+    /// Last block of the range, included.
+    #[arg(long, default_value = "latest")]
+    pub to: String,
+    /// Only events emitted by this contract.
+    #[arg(long, value_parser = parse_felt)]
+    pub contract: Option<FieldElement>,
+    /// Only events with one of these first keys, as event names or felts.
+    #[arg(long = "key", value_parser = parse_selector)]
+    pub keys: Vec<FieldElement>,
+    /// Maximum number of events returned.
+    #[arg(long, default_value_t = 100)]
+    pub limit: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct CallArgs {
+    /// Address of the contract.
+    #[arg(value_parser = parse_felt)]
+    pub contract: FieldElement,
+    /// Entrypoint name, or selector as a felt.
+    #[arg(value_parser = parse_selector)]
+    pub selector: FieldElement,
+    /// Calldata, as felts.
+    #[arg(value_parser = parse_felt)]
+    pub calldata: Vec<FieldElement>,
+    /// Block number, hash, `latest` or `pending`.
+    #[arg(long, default_value = "latest")]
+    pub block: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DecodeCommand {
+    /// Decodes a short string, a legacy long string or a `ByteArray`.
+    String {
+        #[arg(value_parser = parse_felt, required = true)]
+        felts: Vec<FieldElement>,
+    },
+    /// Decodes a `u256` from its low and high parts.
+    U256 {
+        #[arg(value_parser = parse_felt)]
+        low: FieldElement,
+        #[arg(value_parser = parse_felt)]
+        high: FieldElement,
+    },
+}
@@ -0,0 +1,131 @@
+//! Rendering of the command results, as JSON or as a table.
+use block_issue_sample::config::SinkFormat;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Table,
+}
+
+impl From<SinkFormat> for OutputFormat {
+    fn from(format: SinkFormat) -> Self {
+        match format {
+            SinkFormat::Json => OutputFormat::Json,
+            SinkFormat::Table => OutputFormat::Table,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Renders a result. Lists of objects are rendered as a table
+    /// with one row per item, objects as a table of fields.
+    pub fn render<T: Serialize>(&self, value: &T) -> anyhow::Result<String> {
+        let value = serde_json::to_value(value)?;
+
+        Ok(match self {
+            OutputFormat::Json => serde_json::to_string_pretty(&value)?,
+            OutputFormat::Table => match &value {
+                Value::Array(items) => items_table(items),
+                Value::Object(fields) => table(
+                    &["field", "value"],
+                    fields
+                        .iter()
+                        .map(|(k, v)| vec![k.clone(), cell(v)])
+                        .collect(),
+                ),
+                other => cell(other),
+            },
+        })
+    }
+
+    pub fn print<T: Serialize>(&self, value: &T) -> anyhow::Result<()> {
+        println!("{}", self.render(value)?);
+        Ok(())
+    }
+
+    /// Renders a result on a single line, for streamed results:
+    /// compact JSON, or `field=value` pairs for tables.
+    pub fn render_line<T: Serialize>(&self, value: &T) -> anyhow::Result<String> {
+        let value = serde_json::to_value(value)?;
+
+        Ok(match (self, &value) {
+            (OutputFormat::Json, _) => value.to_string(),
+            (OutputFormat::Table, Value::Object(fields)) => fields
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, cell(v)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            (OutputFormat::Table, other) => cell(other),
+        })
+    }
+}
+
+fn items_table(items: &[Value]) -> String {
+    // Columns in the order of the first item.
+    let headers: Vec<String> = match items.first() {
+        Some(Value::Object(fields)) => fields.keys().cloned().collect(),
+        _ => {
+            let rows = items.iter().map(|v| vec![cell(v)]).collect();
+            return table(&["value"], rows);
+        }
+    };
+
+    let rows = items
+        .iter()
+        .map(|item| {
+            headers
+                .iter()
+                .map(|h| item.get(h).map(cell).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    table(
+        &headers.iter().map(String::as_str).collect::<Vec<_>>(),
+        rows,
+    )
+}
+
+/// Renders a value in a single cell, nested values as compact JSON.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(" "),
+        other => other.to_string(),
+    }
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let line = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{:<w$}", v, w = w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let separators: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+
+    let mut lines = vec![line(headers.to_vec())];
+    lines.push(line(separators.iter().map(String::as_str).collect()));
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+
+    lines.join("\n")
+}
@@ -0,0 +1,225 @@
+//! Readiness of the indexer, reported by `/readyz`.
+use crate::config::HealthConfig;
+use crate::watcher::BlockWatcher;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the result of the checkpoint store check is reused, to not
+/// write to the disk on every probe.
+const CHECKPOINT_STORE_TTL: Duration = Duration::from_secs(10);
+
+/// Result of the last checkpoint store check, and when it was made.
+type CachedCheck = Option<(Instant, Result<(), String>)>;
+
+/// Distinguishes the probe files of concurrent checks.
+static PROBE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Checks the watcher progress against the thresholds, and the store
+/// of the checkpoints. Without a watcher, only the store is checked.
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    config: HealthConfig,
+    watcher: Option<Arc<BlockWatcher>>,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_store: Arc<Mutex<CachedCheck>>,
+}
+
+impl Readiness {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            watcher: None,
+            checkpoint_path: None,
+            checkpoint_store: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_watcher(mut self, watcher: Arc<BlockWatcher>) -> Self {
+        self.watcher = Some(watcher);
+        self
+    }
+
+    pub fn with_checkpoint_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.checkpoint_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn check(&self) -> ReadinessReport {
+        let mut checks = vec![];
+
+        if let Some(watcher) = &self.watcher {
+            let lag_blocks = watcher.lag_blocks();
+            checks.push(Check {
+                name: "lag_blocks",
+                ok: lag_blocks <= self.config.max_lag_blocks,
+                message: format!(
+                    "{} blocks behind, max {}",
+                    lag_blocks, self.config.max_lag_blocks
+                ),
+            });
+
+            let lag_seconds = watcher.lag_seconds();
+            checks.push(Check {
+                name: "lag_seconds",
+                ok: lag_seconds <= self.config.max_lag_seconds,
+                message: format!(
+                    "{}s behind, max {}s",
+                    lag_seconds, self.config.max_lag_seconds
+                ),
+            });
+
+            let rpc_age = watcher.rpc_age_seconds();
+            checks.push(Check {
+                name: "rpc",
+                ok: rpc_age.is_some_and(|age| age <= self.config.max_rpc_age_seconds),
+                message: match rpc_age {
+                    Some(age) => format!(
+                        "last successful call {}s ago, max {}s",
+                        age, self.config.max_rpc_age_seconds
+                    ),
+                    None => "no successful call yet".to_string(),
+                },
+            });
+        }
+
+        if let Some(path) = &self.checkpoint_path {
+            let store = self.checkpoint_store(path);
+            checks.push(Check {
+                name: "checkpoint_store",
+                ok: store.is_ok(),
+                message: store.err().unwrap_or_else(|| "available".to_string()),
+            });
+        }
+
+        ReadinessReport {
+            ready: checks.iter().all(|c| c.ok),
+            checks,
+        }
+    }
+
+    /// Checks the checkpoint store, reusing a result younger than the TTL.
+    fn checkpoint_store(&self, path: &Path) -> Result<(), String> {
+        let mut cached = self
+            .checkpoint_store
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        match &*cached {
+            Some((at, result)) if at.elapsed() < CHECKPOINT_STORE_TTL => result.clone(),
+            _ => {
+                let result = checkpoint_store(path);
+                *cached = Some((Instant::now(), result.clone()));
+                result
+            }
+        }
+    }
+}
+
+/// Checks that the checkpoint can be written: a file must be created in its
+/// directory, and the checkpoint, if any, readable.
+fn checkpoint_store(path: &Path) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let metadata = std::fs::metadata(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    if !metadata.is_dir() {
+        return Err(format!("{}: not a directory", dir.display()));
+    }
+
+    // Permissions alone don't tell about ownership, ACLs or read only mounts.
+    let probe = dir.join(format!(
+        ".readyz-{}-{}.probe",
+        std::process::id(),
+        PROBE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    match std::fs::remove_file(&probe) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("{}: {}", probe.display(), e));
+        }
+        _ => {}
+    }
+
+    if path.exists() {
+        std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_store_probes_the_directory() {
+        let dir = std::env::temp_dir().join(format!("readyz-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(checkpoint_store(&dir.join("checkpoint.json")), Ok(()));
+        // The probe file is removed.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        assert!(checkpoint_store(&dir.join("missing").join("checkpoint.json")).is_err());
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_store_concurrent_probes() {
+        let dir = std::env::temp_dir().join(format!("readyz-concurrent-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || checkpoint_store(&path))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Ok(()));
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_store_result_is_cached() {
+        let dir = std::env::temp_dir().join(format!("readyz-cached-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let readiness = Readiness::new(HealthConfig::default())
+            .with_checkpoint_path(dir.join("checkpoint.json"));
+        assert!(readiness.check().ready);
+
+        // Within the TTL, the directory is not probed again.
+        std::fs::remove_dir(&dir).unwrap();
+        assert!(readiness.check().ready);
+
+        *readiness.checkpoint_store.lock().unwrap() = None;
+        assert!(!readiness.check().ready);
+    }
+}
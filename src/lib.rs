@@ -0,0 +1,9 @@
+pub mod backfill;
+pub mod config;
+pub mod health;
+pub mod logging;
+pub mod metrics;
+pub mod nft;
+pub mod server;
+pub mod starknet_utils;
+pub mod watcher;
@@ -0,0 +1,35 @@
+//! Diagnostics of the indexer, written to stderr with `tracing`
+//! as pretty text or as JSON lines.
+use crate::config::{LogConfig, LogFormat};
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoggingError {
+    #[error("Invalid log level filter: {0}")]
+    Filter(#[from] ParseError),
+    #[error("Can't install the logger: {0}")]
+    Init(#[from] TryInitError),
+}
+
+/// Installs the global subscriber. Events of the RPC client and the watcher
+/// carry the fields of their spans (method, block id, endpoint).
+pub fn init(config: &LogConfig) -> Result<(), LoggingError> {
+    let filter = EnvFilter::try_new(&config.level)?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    match config.format {
+        LogFormat::Pretty => builder.finish().try_init()?,
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .finish()
+            .try_init()?,
+    };
+
+    Ok(())
+}
@@ -1,66 +1,619 @@
-use crate::starknet_utils::client::StarknetClient;
-use starknet::core::types::{BlockId, BlockTag};
-use starknet_utils::client::http::StarknetClientHttp;
+mod cli;
 
-mod starknet_utils;
+use block_issue_sample::backfill::{backfill, BackfillConfig, BlockRange};
+use block_issue_sample::config::{Config, ConfigLoader, SinkConfig, SinkKind};
+use block_issue_sample::health::Readiness;
+use block_issue_sample::logging;
+use block_issue_sample::metrics::Metrics;
+use block_issue_sample::nft::contract_type::ContractType;
+use block_issue_sample::nft::discovery::{CollectionDiscovery, NewCollection};
+use block_issue_sample::server;
+use block_issue_sample::starknet_utils::cairo_string_parser::parse_cairo_string_with_encoding;
+use block_issue_sample::starknet_utils::client::{http::StarknetClientHttp, StarknetClient};
+use block_issue_sample::starknet_utils::CairoU256;
+use block_issue_sample::watcher::{BlockWatcher, RecentBlocks};
+use clap::Parser;
+use serde::Serialize;
+use starknet::core::types::{BlockId, BlockTag, EmittedEvent, FieldElement};
+use starknet::core::types::{BlockStatus, MaybePendingBlockWithTxHashes};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
-const RPC_URL: &str = &"https://starknet-mainnet.public.blastapi.io";
+use cli::output::OutputFormat;
+use cli::{BackfillArgs, CallArgs, Cli, Command, ConfigCommand, DecodeCommand, EventsArgs};
 
-async fn index_pending_blocks() {
-    let client = StarknetClientHttp::new(RPC_URL).unwrap();
-    let mut previous_loop_ts = 0;
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum WatchUpdate {
+    Pending {
+        timestamp: u64,
+        transactions: usize,
+    },
+    Latest {
+        block_number: u64,
+        timestamp: u64,
+    },
+    /// The block was replaced by a reorganization, with the blocks after it.
+    Reorg {
+        block_number: u64,
+    },
+    /// An ERC-721 or ERC-1155 contract deployed in the block.
+    #[serde(rename = "new_collection")]
+    NewCollection {
+        block_number: u64,
+        address: String,
+        class_hash: String,
+        standard: ContractType,
+        deployer: Option<String>,
+        transaction_hash: Option<String>,
+    },
+}
 
-    loop {
+impl From<&NewCollection> for WatchUpdate {
+    fn from(c: &NewCollection) -> Self {
+        WatchUpdate::NewCollection {
+            block_number: c.block_number,
+            address: hex(&c.address),
+            class_hash: hex(&c.class_hash),
+            standard: c.detection.contract_type,
+            deployer: c.deployer.as_ref().map(hex),
+            transaction_hash: c.transaction_hash.as_ref().map(hex),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BlockInfo {
+    number: Option<u64>,
+    hash: Option<String>,
+    parent_hash: String,
+    timestamp: u64,
+    status: String,
+    transactions: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct EventRow {
+    block_number: Option<u64>,
+    transaction_hash: String,
+    from_address: String,
+    keys: Vec<String>,
+    data: Vec<String>,
+}
+
+impl From<&EmittedEvent> for EventRow {
+    fn from(e: &EmittedEvent) -> Self {
+        Self {
+            block_number: e.block_number,
+            transaction_hash: hex(&e.transaction_hash),
+            from_address: hex(&e.from_address),
+            keys: e.keys.iter().map(hex).collect(),
+            data: e.data.iter().map(hex).collect(),
+        }
+    }
+}
+
+fn hex(felt: &FieldElement) -> String {
+    format!("{:#x}", felt)
+}
+
+#[instrument(skip_all, name = "watcher")]
+async fn index_pending_blocks(
+    client: &StarknetClientHttp,
+    config: &Config,
+    output: OutputFormat,
+    watcher: &BlockWatcher,
+    metrics: &Metrics,
+) -> anyhow::Result<()> {
+    let contracts = config.watcher.contracts()?;
+    let keys = config.watcher.keys()?;
+    let mut sink = open_sink(&config.sink)?;
+
+    info!(
+        poll_interval_ms = config.watcher.poll_interval_ms,
+        contracts = contracts.len(),
+        "Watching the pending block"
+    );
+
+    let mut recent_blocks = RecentBlocks::default();
+    let mut discovery = config
+        .watcher
+        .discover_collections
+        .then(CollectionDiscovery::new);
+
+    'poll: loop {
         let (pending_ts, txs) = match client
             .block_txs_hashes(BlockId::Tag(BlockTag::Pending))
             .await
         {
             Ok((ts, txs)) => (ts, txs),
             Err(e) => {
-                println!("Error while fetching pending block txs: {:?}", e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                error!(error = e.variant(), "Can't fetch pending block txs: {}", e);
+                tokio::time::sleep(config.watcher.error_interval()).await;
                 continue;
             }
         };
 
-        if previous_loop_ts == 0 {
-            previous_loop_ts = pending_ts;
-        }
+        let previous_loop_ts = watcher.pending_polled(pending_ts, txs.len());
+        metrics.pending_polled(txs.len());
+
+        debug!(pending_ts, transactions = txs.len(), "Pending block polled");
 
-        println!("Pending block {} with {} txs", pending_ts, txs.len());
+        write_line(
+            &mut sink,
+            output,
+            &WatchUpdate::Pending {
+                timestamp: pending_ts,
+                transactions: txs.len(),
+            },
+        )?;
 
-        // If the timestamp is different from the previous loop,
-        // we must first ensure we've fetched and processed all the transactions
-        // of the previous pending block, which is now the "Latest".
+        // If the timestamp is different from the previous loop, the previous
+        // pending block is now accepted: every block up to the chain head must
+        // be processed before following the new pending block.
         if pending_ts != previous_loop_ts {
-            println!("ts differ! {} {}", pending_ts, previous_loop_ts);
-            // Get the latest block number, generated by the sequencer, which is
-            // expected to be the one we just processed.
-            let block_number = match client.block_number().await {
+            let head = match client.block_number().await {
                 Ok(n) => n,
                 Err(e) => {
-                    println!("Error while fetching latest block number: {:?}", e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                    error!(
+                        error = e.variant(),
+                        "Can't fetch latest block number: {}", e
+                    );
+                    tokio::time::sleep(config.watcher.error_interval()).await;
                     continue;
                 }
             };
 
-            // TODO:  handle on_new_latest_block(block_number) ...
+            metrics.set_chain_head(head);
+            watcher.chain_head_fetched(head);
+
+            // A node lagging behind may return an older head, the blocks
+            // already processed are then kept until it catches up.
+            let mut next = watcher
+                .state()
+                .last_processed_block
+                .map_or(head, |last| last + 1);
+
+            while next <= head {
+                let span = info_span!("latest_block", block_number = next);
+
+                let header = match client
+                    .block_header(BlockId::Number(next))
+                    .instrument(span.clone())
+                    .await
+                {
+                    Ok(header) => header,
+                    Err(e) => {
+                        span.in_scope(|| {
+                            error!(error = e.variant(), "Can't fetch block header: {}", e)
+                        });
+                        tokio::time::sleep(config.watcher.error_interval()).await;
+                        continue 'poll;
+                    }
+                };
+
+                // The parent of the block is not the block processed before it,
+                // which was replaced: it is processed again from the new chain.
+                if !recent_blocks.is_child(next, header.parent_hash) {
+                    let replaced = next - 1;
+                    span.in_scope(|| {
+                        warn!(replaced, parent_hash = %hex(&header.parent_hash), "Chain reorganization detected")
+                    });
+                    metrics.reorgs.inc();
+                    write_line(
+                        &mut sink,
+                        output,
+                        &WatchUpdate::Reorg {
+                            block_number: replaced,
+                        },
+                    )?;
+
+                    recent_blocks.remove_from(replaced);
+                    if let Some(discovery) = &mut discovery {
+                        discovery.forget_from(replaced);
+                    }
+                    watcher.block_replaced(replaced);
+                    next = replaced;
+                    continue;
+                }
+
+                let start = Instant::now();
+
+                // Fetched by hash, as the block at this number may change meanwhile.
+                let events = match client
+                    .fetch_all_block_events(BlockId::Hash(header.hash), keys.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    Ok(events) => events,
+                    Err(e) => {
+                        span.in_scope(|| {
+                            error!(error = e.variant(), "Can't fetch block events: {}", e)
+                        });
+                        tokio::time::sleep(config.watcher.error_interval()).await;
+                        continue 'poll;
+                    }
+                };
 
-            println!(
-                "Pending block {} is now latest block number #{}",
-                previous_loop_ts, block_number
-            );
+                let collections = match &mut discovery {
+                    Some(discovery) => {
+                        // Collections of a previous attempt on this block are found again.
+                        discovery.forget_from(next);
+                        match discovery
+                            .discover_block(client, next)
+                            .instrument(span.clone())
+                            .await
+                        {
+                            Ok(collections) => collections,
+                            Err(e) => {
+                                span.in_scope(|| {
+                                    error!(error = e.variant(), "Can't discover collections: {}", e)
+                                });
+                                tokio::time::sleep(config.watcher.error_interval()).await;
+                                continue 'poll;
+                            }
+                        }
+                    }
+                    None => vec![],
+                };
 
-            previous_loop_ts = pending_ts;
+                // Written once the block is fully fetched, not to be repeated on errors.
+                write_line(
+                    &mut sink,
+                    output,
+                    &WatchUpdate::Latest {
+                        block_number: next,
+                        timestamp: header.timestamp,
+                    },
+                )?;
+
+                for collection in &collections {
+                    write_line(&mut sink, output, &WatchUpdate::from(collection))?;
+                }
+
+                let mut indexed = 0;
+                for event in events.values().flatten() {
+                    if contracts.is_empty() || contracts.contains(&event.from_address) {
+                        write_line(&mut sink, output, &EventRow::from(event))?;
+                        indexed += 1;
+                    }
+                }
+
+                span.in_scope(|| {
+                    info!(
+                        events = indexed,
+                        latency_ms = start.elapsed().as_millis() as u64,
+                        "Latest block indexed"
+                    )
+                });
+                metrics.blocks_processed(next, Some(header.timestamp), indexed);
+                watcher.block_processed(next, header.timestamp);
+                recent_blocks.push(next, header.hash);
+                next += 1;
+            }
+
+            watcher.pending_followed(pending_ts);
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        tokio::time::sleep(config.watcher.poll_interval()).await;
     }
 }
 
-fn main() {
-    tokio::runtime::Runtime::new()
-        .unwrap()
-        .block_on(index_pending_blocks());
+/// Opens the writer of the configured sink. File sinks are appended to.
+fn open_sink(sink: &SinkConfig) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match (sink.kind, &sink.path) {
+        (SinkKind::File, Some(path)) => {
+            Box::new(OpenOptions::new().create(true).append(true).open(path)?)
+        }
+        _ => Box::new(std::io::stdout()),
+    })
+}
+
+fn write_line<T: Serialize>(
+    sink: &mut Box<dyn Write>,
+    output: OutputFormat,
+    value: &T,
+) -> anyhow::Result<()> {
+    writeln!(sink, "{}", output.render_line(value)?)?;
+    sink.flush()?;
+    Ok(())
+}
+
+/// Backfills the events of a block range, written one per line to the sink
+/// in the given output format.
+async fn run_backfill(
+    client: StarknetClientHttp,
+    config: &Config,
+    args: BackfillArgs,
+    output_format: OutputFormat,
+    metrics: &Metrics,
+) -> anyhow::Result<()> {
+    let client = Arc::new(client);
+
+    let (from, to) = client.parse_block_range(&args.from, &args.to)?;
+    let range = BlockRange {
+        from: client.block_id_to_u64(&from).await?,
+        to: client.block_id_to_u64(&to).await?,
+    };
+    metrics.set_chain_head(client.block_number().await?);
+
+    let mut backfill_config = BackfillConfig::new(range);
+    backfill_config.chunk_size = config.backfill.chunk_size;
+    backfill_config.concurrency = config.backfill.concurrency;
+    backfill_config.checkpoint_path = Some(config.backfill.checkpoint_path.clone());
+    backfill_config.keys = config.watcher.keys()?;
+
+    // The sink is appended to, as a resumed backfill continues it.
+    let mut output = BufWriter::new(open_sink(&config.sink)?);
+
+    let total = backfill(client, backfill_config, |chunk| {
+        for event in chunk.events.values().flatten() {
+            let line = output_format
+                .render_line(&EventRow::from(event))
+                .map_err(std::io::Error::other)?;
+            writeln!(output, "{}", line)?;
+        }
+        // Flushed before the checkpoint is written.
+        output.flush()?;
+
+        let events = chunk.events.values().map(Vec::len).sum::<usize>();
+        info!(
+            from = chunk.range.from,
+            to = chunk.range.to,
+            events,
+            "Backfill chunk done"
+        );
+        metrics.blocks_processed(chunk.range.to, None, events);
+        Ok(())
+    })
+    .await?;
+
+    info!(events = total, "Backfill done");
+    Ok(())
+}
+
+async fn run_events(
+    client: &StarknetClientHttp,
+    args: EventsArgs,
+) -> anyhow::Result<Vec<EventRow>> {
+    let (from, to) = client.parse_block_range(&args.from, &args.to)?;
+    let keys = (!args.keys.is_empty()).then(|| vec![args.keys]);
+
+    let mut rows = vec![];
+    let mut continuation_token = None;
+
+    loop {
+        let page = client
+            .fetch_events(
+                Some(from),
+                Some(to),
+                keys.clone(),
+                args.contract,
+                continuation_token,
+            )
+            .await?;
+
+        let mut blocks: Vec<_> = page.events.into_iter().collect();
+        blocks.sort_by_key(|(block_number, _)| *block_number);
+        rows.extend(blocks.iter().flat_map(|(_, e)| e).map(EventRow::from));
+
+        continuation_token = page.continuation_token;
+        if continuation_token.is_none() || rows.len() >= args.limit {
+            break;
+        }
+    }
+
+    rows.truncate(args.limit);
+    Ok(rows)
+}
+
+async fn run_call(client: &StarknetClientHttp, args: CallArgs) -> anyhow::Result<Vec<String>> {
+    let block = client.parse_block_id(&args.block)?;
+
+    let felts = client
+        .call_contract(args.contract, args.selector, args.calldata, block)
+        .await?;
+
+    Ok(felts.iter().map(hex).collect())
+}
+
+async fn run_block(client: &StarknetClientHttp, id: &str) -> anyhow::Result<BlockInfo> {
+    let block_id = client.parse_block_id(id)?;
+
+    let info = match client.block_with_tx_hashes(block_id).await? {
+        MaybePendingBlockWithTxHashes::Block(b) => BlockInfo {
+            number: Some(b.block_number),
+            hash: Some(hex(&b.block_hash)),
+            parent_hash: hex(&b.parent_hash),
+            timestamp: b.timestamp,
+            status: match b.status {
+                BlockStatus::Pending => "pending",
+                BlockStatus::AcceptedOnL2 => "accepted_on_l2",
+                BlockStatus::AcceptedOnL1 => "accepted_on_l1",
+                BlockStatus::Rejected => "rejected",
+            }
+            .to_string(),
+            transactions: b.transactions.len(),
+        },
+        MaybePendingBlockWithTxHashes::PendingBlock(b) => BlockInfo {
+            number: None,
+            hash: None,
+            parent_hash: hex(&b.parent_hash),
+            timestamp: b.timestamp,
+            status: "pending".to_string(),
+            transactions: b.transactions.len(),
+        },
+    };
+
+    Ok(info)
+}
+
+fn run_decode(value: DecodeCommand) -> anyhow::Result<serde_json::Value> {
+    Ok(match value {
+        DecodeCommand::String { felts } => {
+            let (value, encoding) = parse_cairo_string_with_encoding(&felts)
+                .map_err(|e| anyhow::anyhow!("Can't decode string: {:?}", e))?;
+            serde_json::json!({ "value": value, "encoding": encoding })
+        }
+        DecodeCommand::U256 { low, high } => {
+            let value = CairoU256::from_felts(low, high)?;
+            serde_json::json!({ "decimal": value.to_decimal(false), "hex": value.to_hex() })
+        }
+    })
+}
+
+/// Loads the configuration layers, the command line flags being the last one.
+fn load_config(cli: &Cli) -> anyhow::Result<Config> {
+    let mut loader = ConfigLoader::new();
+    if let Some(path) = &cli.config {
+        loader = loader.file(path);
+    }
+    loader = loader.env();
+
+    if let Some(network) = &cli.network {
+        loader = loader.set("rpc.network", network.as_str());
+    }
+    if let Some(url) = &cli.rpc_url {
+        loader = loader.set("rpc.url", url.as_str());
+    }
+    if let Some(output) = cli.output {
+        let format = match output {
+            OutputFormat::Json => "json",
+            OutputFormat::Table => "table",
+        };
+        loader = loader.set("sink.format", format);
+    }
+    if let Some(level) = &cli.log_level {
+        loader = loader.set("log.level", level.as_str());
+    }
+    if let Some(format) = &cli.log_format {
+        loader = loader.set("log.format", format.as_str());
+    }
+    if let Some(address) = &cli.metrics_address {
+        loader = loader
+            .set("metrics.enabled", true)
+            .set("metrics.address", address.as_str());
+    }
+    if let Some(address) = &cli.health_address {
+        loader = loader
+            .set("health.enabled", true)
+            .set("health.address", address.as_str());
+    }
+
+    if let Command::Backfill(args) = &cli.command {
+        if let Some(chunk_size) = args.chunk_size {
+            loader = loader.set("backfill.chunk_size", chunk_size as i64);
+        }
+        if let Some(concurrency) = args.concurrency {
+            loader = loader.set("backfill.concurrency", concurrency as i64);
+        }
+        if let Some(checkpoint) = &args.checkpoint {
+            loader = loader.set("backfill.checkpoint_path", checkpoint.display().to_string());
+        }
+        if let Some(path) = &args.out_file {
+            loader = loader
+                .set("sink.kind", "file")
+                .set("sink.path", path.display().to_string());
+        }
+    }
+
+    Ok(loader.load()?)
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = load_config(&cli)?;
+    logging::init(&config.log)?;
+    let output = OutputFormat::from(config.sink.format);
+
+    match cli.command {
+        Command::Decode { value } => return output.print(&run_decode(value)?),
+        Command::Config {
+            command: ConfigCommand::Print,
+        } => {
+            let config = config.redacted();
+            match output {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&config)?),
+                OutputFormat::Table => print!("{}", config.to_toml()?),
+            };
+            return Ok(());
+        }
+        _ => (),
+    };
+
+    let metrics = Arc::new(Metrics::new());
+    let client = StarknetClientHttp::with_config(
+        config.rpc.url(),
+        config.rpc.events_chunk_size,
+        config.rpc.retry,
+    )?
+    .with_metrics(metrics.clone());
+
+    let watcher = Arc::new(BlockWatcher::new());
+    let mut readiness = Readiness::new(config.health.clone())
+        .with_checkpoint_path(&config.backfill.checkpoint_path);
+    if let Command::Watch = cli.command {
+        readiness = readiness.with_watcher(watcher.clone());
+    }
+    let readiness = Arc::new(readiness);
+
+    // The metrics and the health are served while the command runs,
+    // by a single server when they share the same address.
+    let metrics_address = match config.metrics.enabled {
+        true => Some(config.metrics.address()?),
+        false => None,
+    };
+    let health_address = match (config.health.enabled, &cli.command) {
+        (true, Command::Watch | Command::Backfill(_)) => Some(config.health.address()?),
+        _ => None,
+    };
+
+    let mut servers = JoinSet::new();
+    if let Some(address) = metrics_address {
+        let shared = (health_address == Some(address)).then(|| readiness.clone());
+        servers.spawn(server::serve(address, Some(metrics.clone()), shared));
+    }
+    if let Some(address) = health_address.filter(|a| Some(*a) != metrics_address) {
+        servers.spawn(server::serve(address, None, Some(readiness)));
+    }
+
+    let command = async {
+        match cli.command {
+            Command::Watch => {
+                index_pending_blocks(&client, &config, output, &watcher, &metrics).await?
+            }
+            Command::Backfill(args) => {
+                run_backfill(client, &config, args, output, &metrics).await?
+            }
+            Command::Events(args) => output.print(&run_events(&client, args).await?)?,
+            Command::Call(args) => output.print(&run_call(&client, args).await?)?,
+            Command::Block { id } => output.print(&run_block(&client, &id).await?)?,
+            Command::Decode { .. } | Command::Config { .. } => {
+                unreachable!("run without client")
+            }
+        };
+        Ok::<_, anyhow::Error>(())
+    };
+
+    // A server failing, like on an address already in use, doesn't stop the command.
+    tokio::pin!(command);
+    loop {
+        tokio::select! {
+            Some(r) = servers.join_next() => {
+                if let Err(e) = r? {
+                    error!("HTTP server stopped, the command goes on: {}", e);
+                }
+            }
+            r = &mut command => return r,
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    tokio::runtime::Runtime::new()?.block_on(run(cli))
 }
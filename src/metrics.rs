@@ -0,0 +1,247 @@
+//! Prometheus metrics of the indexer, served by [`crate::server`].
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const NAMESPACE: &str = "indexer";
+
+/// Buckets of the RPC latency, in seconds.
+const RPC_LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Latest block number of the chain.
+    pub chain_head: IntGauge,
+    pub last_processed_block: IntGauge,
+    pub last_processed_timestamp: IntGauge,
+    /// Blocks between the chain head and the last processed block.
+    pub lag_blocks: IntGauge,
+    /// Seconds between now and the timestamp of the last processed block,
+    /// updated when rendered.
+    pub lag_seconds: Gauge,
+    pub pending_transactions: IntGauge,
+    /// RPC requests by client method.
+    pub rpc_requests: IntCounterVec,
+    /// RPC latency in seconds by client method, retries included.
+    pub rpc_latency: HistogramVec,
+    /// RPC errors by client method and `StarknetClientError` variant.
+    pub rpc_errors: IntCounterVec,
+    pub reorgs: IntCounter,
+    pub events: IntCounter,
+    /// Events of the last processed blocks over the time since the previous ones.
+    pub events_per_second: Gauge,
+    last_processed_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let chain_head =
+            IntGauge::with_opts(opts("chain_head", "Latest block number of the chain"))
+                .expect("valid metric");
+        let last_processed_block = IntGauge::with_opts(opts(
+            "last_processed_block",
+            "Number of the last processed block",
+        ))
+        .expect("valid metric");
+        let last_processed_timestamp = IntGauge::with_opts(opts(
+            "last_processed_timestamp",
+            "Timestamp of the last processed block",
+        ))
+        .expect("valid metric");
+        let lag_blocks = IntGauge::with_opts(opts(
+            "lag_blocks",
+            "Blocks between the chain head and the last processed block",
+        ))
+        .expect("valid metric");
+        let lag_seconds = Gauge::with_opts(opts(
+            "lag_seconds",
+            "Seconds since the timestamp of the last processed block",
+        ))
+        .expect("valid metric");
+        let pending_transactions = IntGauge::with_opts(opts(
+            "pending_transactions",
+            "Transactions of the pending block",
+        ))
+        .expect("valid metric");
+        let rpc_requests = IntCounterVec::new(
+            opts("rpc_requests_total", "RPC requests by client method"),
+            &["method"],
+        )
+        .expect("valid metric");
+        let rpc_latency = HistogramVec::new(
+            HistogramOpts::from(opts(
+                "rpc_request_duration_seconds",
+                "RPC request latency by client method, retries included",
+            ))
+            .buckets(RPC_LATENCY_BUCKETS.to_vec()),
+            &["method"],
+        )
+        .expect("valid metric");
+        let rpc_errors = IntCounterVec::new(
+            opts(
+                "rpc_errors_total",
+                "RPC errors by client method and error variant",
+            ),
+            &["method", "error"],
+        )
+        .expect("valid metric");
+        let reorgs = IntCounter::with_opts(opts("reorgs_total", "Chain reorganizations detected"))
+            .expect("valid metric");
+        let events =
+            IntCounter::with_opts(opts("events_total", "Events processed")).expect("valid metric");
+        let events_per_second = Gauge::with_opts(opts(
+            "events_per_second",
+            "Events of the last processed blocks per second since the previous ones",
+        ))
+        .expect("valid metric");
+
+        let metrics = Self {
+            registry,
+            chain_head,
+            last_processed_block,
+            last_processed_timestamp,
+            lag_blocks,
+            lag_seconds,
+            pending_transactions,
+            rpc_requests,
+            rpc_latency,
+            rpc_errors,
+            reorgs,
+            events,
+            events_per_second,
+            last_processed_at: Arc::new(Mutex::new(None)),
+        };
+
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(self.chain_head.clone()),
+            Box::new(self.last_processed_block.clone()),
+            Box::new(self.last_processed_timestamp.clone()),
+            Box::new(self.lag_blocks.clone()),
+            Box::new(self.lag_seconds.clone()),
+            Box::new(self.pending_transactions.clone()),
+            Box::new(self.rpc_requests.clone()),
+            Box::new(self.rpc_latency.clone()),
+            Box::new(self.rpc_errors.clone()),
+            Box::new(self.reorgs.clone()),
+            Box::new(self.events.clone()),
+            Box::new(self.events_per_second.clone()),
+        ];
+
+        for collector in collectors {
+            // Names are unique and static, registering can't fail.
+            self.registry.register(collector).expect("unique metric");
+        }
+    }
+
+    /// Records a request of a client method, with its error variant if it failed.
+    pub fn observe_rpc(&self, method: &str, latency: Duration, error: Option<&str>) {
+        self.rpc_requests.with_label_values(&[method]).inc();
+        self.rpc_latency
+            .with_label_values(&[method])
+            .observe(latency.as_secs_f64());
+
+        if let Some(error) = error {
+            self.rpc_errors.with_label_values(&[method, error]).inc();
+        }
+    }
+
+    /// Records the chain head, updating the lag.
+    pub fn set_chain_head(&self, block_number: u64) {
+        self.chain_head.set(block_number as i64);
+        self.update_lag_blocks();
+    }
+
+    /// Records the transactions of the pending block polled.
+    pub fn pending_polled(&self, transactions: usize) {
+        self.pending_transactions.set(transactions as i64);
+    }
+
+    /// Records the blocks processed up to `block_number` and their events,
+    /// with the timestamp of the last one if known. The events per second
+    /// are computed over the time since the previous call.
+    pub fn blocks_processed(&self, block_number: u64, timestamp: Option<u64>, events: usize) {
+        self.last_processed_block.set(block_number as i64);
+        self.events.inc_by(events as u64);
+
+        if let Some(timestamp) = timestamp {
+            self.last_processed_timestamp.set(timestamp as i64);
+        }
+
+        let now = Instant::now();
+        let mut last_processed_at = self
+            .last_processed_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(previous) = last_processed_at.replace(now) {
+            let elapsed = now.duration_since(previous);
+            if !elapsed.is_zero() {
+                self.events_per_second
+                    .set(events as f64 / elapsed.as_secs_f64());
+            }
+        }
+
+        self.update_lag_blocks();
+    }
+
+    fn update_lag_blocks(&self) {
+        let lag = self.chain_head.get() - self.last_processed_block.get();
+        self.lag_blocks.set(lag.max(0));
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        // Computed now to keep growing while the indexer is stalled.
+        let timestamp = self.last_processed_timestamp.get();
+        if timestamp > 0 {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            self.lag_seconds.set((now - timestamp).max(0) as f64);
+        }
+
+        let mut buffer = vec![];
+        // Encoding in memory only fails on invalid metrics.
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("valid metrics");
+        String::from_utf8(buffer).expect("text format is utf8")
+    }
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_polled_sets_the_gauge() {
+        let metrics = Metrics::new();
+        metrics.pending_polled(12);
+        assert_eq!(metrics.pending_transactions.get(), 12);
+        assert!(metrics.render().contains("indexer_pending_transactions 12"));
+
+        metrics.pending_polled(0);
+        assert_eq!(metrics.pending_transactions.get(), 0);
+    }
+}
@@ -0,0 +1,15 @@
+pub mod classifier;
+pub mod collection;
+pub mod contract_type;
+pub mod discovery;
+pub mod events;
+pub mod fetcher;
+pub mod ledger;
+pub mod metadata;
+pub mod ownership;
+pub mod registry;
+pub mod royalty;
+pub mod sale;
+#[cfg(test)]
+mod test_fixtures;
+pub mod token_uri;
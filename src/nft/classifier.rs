@@ -0,0 +1,312 @@
+//! Classification of token transfers into mints, burns, regular transfers
+//! and transfers happening alongside a payment (sales).
+//!
+//! The classification works over all the events of one transaction,
+//! as returned by `events_from_tx_receipt` without keys filter. The last
+//! event of such a transaction is the transfer of its fee to the sequencer,
+//! which is not a payment.
+use starknet::core::types::{EmittedEvent, FieldElement};
+use starknet::macros::felt;
+use std::collections::{HashMap, HashSet};
+
+use super::contract_type::{ContractType, ContractTypeDetection};
+use super::events::{decode_contract_event, TokenStandard, TokenTransfer, TRANSFER_SELECTOR};
+use crate::starknet_utils::CairoU256;
+
+/// ETH ERC-20 contract address on Starknet mainnet and testnets.
+pub const ETH_ADDRESS: FieldElement =
+    felt!("0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7");
+/// STRK ERC-20 contract address on Starknet mainnet and testnets.
+pub const STRK_ADDRESS: FieldElement =
+    felt!("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d");
+/// Commonly used dead address for burns.
+pub const DEAD_ADDRESS: FieldElement = felt!("0xdead");
+
+/// An ERC-20 transfer of a payment token.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PaymentTransfer {
+    pub currency: FieldElement,
+    pub from: FieldElement,
+    pub to: FieldElement,
+    pub amount: CairoU256,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TokenEventKind {
+    /// Transfer from the zero address. `payments` contains the payments
+    /// made by the receiver in the same transaction, if any.
+    Mint { payments: Vec<PaymentTransfer> },
+    /// Transfer to the zero address, or to a dead address if `dead_address` is true.
+    Burn { dead_address: bool },
+    /// Transfer alongside payments from the receiver to the sender
+    /// in the same transaction.
+    Sale { payments: Vec<PaymentTransfer> },
+    /// Any other transfer.
+    Transfer,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassifiedTransfer {
+    pub transfer: TokenTransfer,
+    pub kind: TokenEventKind,
+}
+
+/// Classifies token transfers, given the known payment tokens, fee tokens
+/// and dead addresses.
+#[derive(Debug, Clone)]
+pub struct TransferClassifier {
+    pub payment_tokens: HashSet<FieldElement>,
+    /// Tokens in which the transaction fees are paid.
+    pub fee_tokens: HashSet<FieldElement>,
+    pub dead_addresses: HashSet<FieldElement>,
+}
+
+impl Default for TransferClassifier {
+    fn default() -> Self {
+        Self {
+            payment_tokens: HashSet::from([ETH_ADDRESS, STRK_ADDRESS]),
+            fee_tokens: HashSet::from([ETH_ADDRESS, STRK_ADDRESS]),
+            dead_addresses: HashSet::from([DEAD_ADDRESS]),
+        }
+    }
+}
+
+impl TransferClassifier {
+    pub fn new(
+        payment_tokens: HashSet<FieldElement>,
+        fee_tokens: HashSet<FieldElement>,
+        dead_addresses: HashSet<FieldElement>,
+    ) -> Self {
+        Self {
+            payment_tokens,
+            fee_tokens,
+            dead_addresses,
+        }
+    }
+
+    /// Classifies every token transfer found in the events of one transaction.
+    ///
+    /// Only the transfers of contracts known as ERC-721 or ERC-1155 are classified,
+    /// as a Cairo 0 ERC-20 `Transfer` has the same layout as an ERC-721 `Transfer`.
+    /// The events are decoded with the layout of the detection, when known.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - All the events of the transaction.
+    /// * `detections` - Standards of the contracts emitting the events, as given
+    ///   by `detect_contract_type` or the class hash registry.
+    pub fn classify_transaction(
+        &self,
+        events: &[EmittedEvent],
+        detections: &HashMap<FieldElement, ContractTypeDetection>,
+    ) -> Vec<ClassifiedTransfer> {
+        let payments = self.payment_transfers(events);
+
+        events
+            .iter()
+            .filter(|e| !self.payment_tokens.contains(&e.from_address))
+            .filter_map(|e| {
+                let detection = detections.get(&e.from_address)?;
+                let standard = token_standard(detection.contract_type);
+                let event = decode_contract_event(e, standard, detection.layout).ok()??;
+                Some((event, detection.contract_type))
+            })
+            .flat_map(|(event, contract_type)| {
+                event
+                    .transfers()
+                    .into_iter()
+                    .filter(move |t| is_standard(contract_type, t.standard))
+            })
+            .map(|transfer| ClassifiedTransfer {
+                kind: self.classify(&transfer, &payments),
+                transfer,
+            })
+            .collect()
+    }
+
+    /// Classifies one token transfer, given the payments of the same transaction.
+    pub fn classify(
+        &self,
+        transfer: &TokenTransfer,
+        payments: &[PaymentTransfer],
+    ) -> TokenEventKind {
+        if transfer.from == FieldElement::ZERO {
+            return TokenEventKind::Mint {
+                payments: payments
+                    .iter()
+                    .filter(|p| p.from == transfer.to)
+                    .cloned()
+                    .collect(),
+            };
+        }
+
+        if transfer.to == FieldElement::ZERO {
+            return TokenEventKind::Burn {
+                dead_address: false,
+            };
+        }
+
+        if self.dead_addresses.contains(&transfer.to) {
+            return TokenEventKind::Burn { dead_address: true };
+        }
+
+        let sale_payments: Vec<PaymentTransfer> = payments
+            .iter()
+            .filter(|p| p.from == transfer.to && p.to == transfer.from)
+            .cloned()
+            .collect();
+
+        if sale_payments.is_empty() {
+            TokenEventKind::Transfer
+        } else {
+            TokenEventKind::Sale {
+                payments: sale_payments,
+            }
+        }
+    }
+
+    /// Returns the ERC-20 transfers of payment tokens found in the events,
+    /// without the fee transfer.
+    pub fn payment_transfers(&self, events: &[EmittedEvent]) -> Vec<PaymentTransfer> {
+        let events = match events.split_last() {
+            Some((last, others)) if self.is_fee_transfer(last) => others,
+            _ => events,
+        };
+
+        events
+            .iter()
+            .filter(|e| self.payment_tokens.contains(&e.from_address))
+            .filter_map(decode_erc20_transfer)
+            .collect()
+    }
+
+    /// The fee of a transaction is transferred to the sequencer by the
+    /// last event of its receipt, emitted by the fee token.
+    fn is_fee_transfer(&self, event: &EmittedEvent) -> bool {
+        self.fee_tokens.contains(&event.from_address) && decode_erc20_transfer(event).is_some()
+    }
+}
+
+fn is_standard(contract_type: ContractType, standard: TokenStandard) -> bool {
+    token_standard(contract_type) == Some(standard)
+}
+
+fn token_standard(contract_type: ContractType) -> Option<TokenStandard> {
+    match contract_type {
+        ContractType::ERC721 => Some(TokenStandard::ERC721),
+        ContractType::ERC1155 => Some(TokenStandard::ERC1155),
+        ContractType::ERC20 | ContractType::Other => None,
+    }
+}
+
+/// Decodes an ERC-20 `Transfer` event, `[from, to, amount_low, amount_high]`
+/// once keys (without selector) and data are concatenated.
+pub fn decode_erc20_transfer(event: &EmittedEvent) -> Option<PaymentTransfer> {
+    if event.keys.first() != Some(&TRANSFER_SELECTOR) {
+        return None;
+    }
+
+    let fields: Vec<FieldElement> = event.keys[1..]
+        .iter()
+        .chain(event.data.iter())
+        .copied()
+        .collect();
+
+    if fields.len() != 4 {
+        return None;
+    }
+
+    Some(PaymentTransfer {
+        currency: event.from_address,
+        from: fields[0],
+        to: fields[1],
+        amount: CairoU256::from_felts(fields[2], fields[3]).ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nft::events::EventLayout;
+    use crate::nft::test_fixtures::*;
+
+    #[test]
+    fn fee_transfer_is_not_a_payment() {
+        let classifier = TransferClassifier::default();
+        // The buyer sends the token to someone, and pays the fee of the transaction.
+        let events = [
+            transfer(COLLECTION, BUYER, SELLER),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+
+        let classified = classifier.classify_transaction(&events, &detections());
+
+        assert!(classifier.payment_transfers(&events).is_empty());
+        assert_eq!(classified.len(), 1);
+        assert_eq!(classified[0].kind, TokenEventKind::Transfer);
+    }
+
+    #[test]
+    fn sale_requires_payment_from_receiver_to_sender() {
+        let classifier = TransferClassifier::default();
+        let fee = payment(BUYER, SEQUENCER, 10);
+
+        let paid = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment(BUYER, SELLER, 100),
+            fee.clone(),
+        ];
+        let classified = classifier.classify_transaction(&paid, &detections());
+        assert_eq!(
+            classified[0].kind,
+            TokenEventKind::Sale {
+                payments: classifier.payment_transfers(&paid)
+            }
+        );
+
+        // The receiver pays a third party, the sender receives nothing.
+        let unrelated = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment(BUYER, felt!("0x3"), 100),
+            fee,
+        ];
+        let classified = classifier.classify_transaction(&unrelated, &detections());
+        assert_eq!(classified[0].kind, TokenEventKind::Transfer);
+    }
+
+    #[test]
+    fn erc20_transfer_of_unknown_contract_is_not_a_token_transfer() {
+        let classifier = TransferClassifier::default();
+        // A swap: the seller sends an ERC-20 and receives ETH.
+        let events = [
+            transfer(ERC20, SELLER, BUYER),
+            payment(BUYER, SELLER, 100),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+
+        assert!(classifier
+            .classify_transaction(&events, &HashMap::new())
+            .is_empty());
+        assert!(classifier
+            .classify_transaction(&events, &detections())
+            .is_empty());
+    }
+
+    #[test]
+    fn events_are_decoded_with_the_known_layout() {
+        let classifier = TransferClassifier::default();
+        let events = [transfer(COLLECTION, SELLER, BUYER)];
+
+        let mut detections = detections();
+        assert_eq!(
+            classifier.classify_transaction(&events, &detections).len(),
+            1
+        );
+
+        // A registered Cairo 1 collection never emits a Cairo 0 `Transfer`.
+        detections.get_mut(&COLLECTION).unwrap().layout = Some(EventLayout::Cairo1);
+        assert!(classifier
+            .classify_transaction(&events, &detections)
+            .is_empty());
+    }
+}
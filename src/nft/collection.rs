@@ -0,0 +1,235 @@
+//! Fetching of collection metadata stored on-chain (name, symbol, total supply).
+use starknet::core::types::{BlockId, FieldElement};
+use starknet::macros::selector;
+
+use crate::starknet_utils::cairo_string_parser::{parse_cairo_string, ParseError};
+use crate::starknet_utils::client::{call_contract_selectors, StarknetClient, StarknetClientError};
+use crate::starknet_utils::CairoU256;
+
+pub const NAME_SELECTORS: [FieldElement; 1] = [selector!("name")];
+pub const SYMBOL_SELECTORS: [FieldElement; 1] = [selector!("symbol")];
+pub const TOTAL_SUPPLY_SELECTORS: [FieldElement; 2] =
+    [selector!("total_supply"), selector!("totalSupply")];
+
+/// Error of a single field of `CollectionInfo`.
+#[derive(Debug, thiserror::Error)]
+pub enum CollectionInfoError {
+    #[error("Call failed: {0}")]
+    Client(#[from] StarknetClientError),
+    #[error("Can't parse string: {0:?}")]
+    Parse(ParseError),
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+}
+
+/// Collection metadata. Each field is fetched independently, a collection
+/// may implement only a subset of them.
+#[derive(Debug)]
+pub struct CollectionInfo {
+    pub address: FieldElement,
+    pub name: Result<String, CollectionInfoError>,
+    pub symbol: Result<String, CollectionInfoError>,
+    pub total_supply: Result<CairoU256, CollectionInfoError>,
+}
+
+/// Fetches the name, symbol and total supply of a collection.
+/// Strings can be short strings, legacy `Array<felt252>` long strings or `ByteArray`.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `address` - The address of the collection contract.
+/// * `block` - The block at which the contract is called.
+pub async fn fetch_collection_info<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    block: BlockId,
+) -> CollectionInfo {
+    let (name, symbol, total_supply) = tokio::join!(
+        fetch_string(client, address, &NAME_SELECTORS, block),
+        fetch_string(client, address, &SYMBOL_SELECTORS, block),
+        fetch_total_supply(client, address, block),
+    );
+
+    CollectionInfo {
+        address,
+        name,
+        symbol,
+        total_supply,
+    }
+}
+
+async fn fetch_string<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    selectors: &[FieldElement],
+    block: BlockId,
+) -> Result<String, CollectionInfoError> {
+    let felts = call_contract_selectors(client, address, selectors, vec![], block).await?;
+    parse_cairo_string(felts).map_err(CollectionInfoError::Parse)
+}
+
+/// Total supply may be returned as a `u256` or as a single felt.
+async fn fetch_total_supply<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    block: BlockId,
+) -> Result<CairoU256, CollectionInfoError> {
+    let felts =
+        call_contract_selectors(client, address, &TOTAL_SUPPLY_SELECTORS, vec![], block).await?;
+
+    match felts[..] {
+        [value] => Ok(CairoU256::from(value)),
+        [low, high] => CairoU256::from_felts(low, high)
+            .map_err(|e| CollectionInfoError::InvalidValue(e.to_string())),
+        _ => Err(CollectionInfoError::InvalidValue(format!(
+            "unexpected total supply length {}",
+            felts.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::byte_array::ByteArray;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::core::utils::cairo_short_string_to_felt;
+    use starknet::macros::felt;
+
+    const COLLECTION: FieldElement = felt!("0xc011");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    /// A contract answering each of the given entrypoints, an error being
+    /// a revert reason.
+    fn mock(
+        entrypoints: Vec<(FieldElement, Result<Vec<FieldElement>, String>)>,
+    ) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(move |contract, selector, calldata, _| {
+                assert_eq!(contract, COLLECTION);
+                assert!(calldata.is_empty());
+                match entrypoints.iter().find(|(s, _)| *s == selector) {
+                    Some((_, result)) => result.clone().map_err(StarknetClientError::Contract),
+                    None => Err(StarknetClientError::EntrypointNotFound("".to_string())),
+                }
+            });
+        client
+    }
+
+    fn short_string(s: &str) -> FieldElement {
+        cairo_short_string_to_felt(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cairo1_collection() {
+        let client = mock(vec![
+            (
+                selector!("name"),
+                Ok(ByteArray::from_string("A collection with a long name").to_felts()),
+            ),
+            (selector!("symbol"), Ok(vec![short_string("COL")])),
+            (
+                selector!("total_supply"),
+                Ok(vec![FieldElement::from(10_000_u64), FieldElement::ZERO]),
+            ),
+        ]);
+
+        let info = fetch_collection_info(&client, COLLECTION, BLOCK).await;
+
+        assert_eq!(info.address, COLLECTION);
+        assert_eq!(info.name.unwrap(), "A collection with a long name");
+        assert_eq!(info.symbol.unwrap(), "COL");
+        assert_eq!(info.total_supply.unwrap(), CairoU256::from(10_000_u64));
+    }
+
+    #[tokio::test]
+    async fn cairo0_collection() {
+        let client = mock(vec![
+            // Legacy long string.
+            (
+                selector!("name"),
+                Ok(vec![
+                    felt!("0x2"),
+                    short_string("Cairo 0 "),
+                    short_string("collection"),
+                ]),
+            ),
+            (selector!("symbol"), Ok(vec![short_string("C0")])),
+            // camelCase entrypoint returning a felt.
+            (selector!("totalSupply"), Ok(vec![felt!("0x2a")])),
+        ]);
+
+        let info = fetch_collection_info(&client, COLLECTION, BLOCK).await;
+
+        assert_eq!(info.name.unwrap(), "Cairo 0 collection");
+        assert_eq!(info.symbol.unwrap(), "C0");
+        assert_eq!(info.total_supply.unwrap(), CairoU256::from(42_u64));
+    }
+
+    #[tokio::test]
+    async fn fields_fail_independently() {
+        let client = mock(vec![
+            (selector!("name"), Ok(vec![short_string("Partial")])),
+            (selector!("symbol"), Ok(vec![])),
+            (
+                selector!("total_supply"),
+                Err("Error in the called contract".to_string()),
+            ),
+        ]);
+
+        let info = fetch_collection_info(&client, COLLECTION, BLOCK).await;
+
+        assert_eq!(info.name.unwrap(), "Partial");
+        assert!(matches!(
+            info.symbol,
+            Err(CollectionInfoError::Parse(ParseError::NoValueFound))
+        ));
+        assert!(matches!(
+            info.total_supply,
+            Err(CollectionInfoError::Client(StarknetClientError::Contract(
+                _
+            )))
+        ));
+
+        let info = fetch_collection_info(&mock(vec![]), COLLECTION, BLOCK).await;
+
+        assert!(matches!(
+            info.name,
+            Err(CollectionInfoError::Client(
+                StarknetClientError::EntrypointNotFound(_)
+            ))
+        ));
+        assert!(matches!(
+            info.total_supply,
+            Err(CollectionInfoError::Client(
+                StarknetClientError::EntrypointNotFound(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_total_supply() {
+        for felts in [
+            vec![],
+            vec![felt!("0x1"), felt!("0x2"), felt!("0x3")],
+            // The low limb doesn't fit in 128 bits.
+            vec![
+                felt!("0x100000000000000000000000000000000"),
+                FieldElement::ZERO,
+            ],
+        ] {
+            let client = mock(vec![(selector!("total_supply"), Ok(felts))]);
+
+            let info = fetch_collection_info(&client, COLLECTION, BLOCK).await;
+
+            assert!(matches!(
+                info.total_supply,
+                Err(CollectionInfoError::InvalidValue(_))
+            ));
+        }
+    }
+}
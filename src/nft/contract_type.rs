@@ -0,0 +1,309 @@
+//! Detection of the standard implemented by a contract.
+//!
+//! The detection first relies on SRC5 `supports_interface`, and falls back
+//! on probing the entrypoints of the contract when SRC5 is not supported,
+//! which is the case for most of the Cairo 0 contracts.
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{BlockId, FieldElement};
+use starknet::macros::{felt, selector};
+
+use super::events::EventLayout;
+use crate::starknet_utils::client::{call_contract_selectors, StarknetClient, StarknetClientError};
+
+/// SRC5 interface id of ERC-721.
+pub const IERC721_ID: FieldElement =
+    felt!("0x33eb2f84c309543403fd69f0d0f363781ef06ef6faeb0131ff16ea3175bd943");
+/// ERC-165 interface id of ERC-721, used by older contracts.
+pub const IERC721_ID_LEGACY: FieldElement = felt!("0x80ac58cd");
+/// SRC5 interface id of ERC-1155.
+pub const IERC1155_ID: FieldElement =
+    felt!("0x6114a8f75559e1b39fcba08ce02961a1aa082d9256a158dd3e64964e4b1b52");
+/// ERC-165 interface id of ERC-1155, used by older contracts.
+pub const IERC1155_ID_LEGACY: FieldElement = felt!("0xd9b67a26");
+
+pub const SUPPORTS_INTERFACE_SELECTORS: [FieldElement; 2] = [
+    selector!("supports_interface"),
+    selector!("supportsInterface"),
+];
+pub const OWNER_OF_SELECTORS: [FieldElement; 2] = [selector!("owner_of"), selector!("ownerOf")];
+pub const BALANCE_OF_SELECTORS: [FieldElement; 2] =
+    [selector!("balance_of"), selector!("balanceOf")];
+pub const TOKEN_URI_SELECTORS: [FieldElement; 2] = [selector!("token_uri"), selector!("tokenURI")];
+/// Entrypoints of the Cairo 0 proxies returning their implementation.
+pub const IMPLEMENTATION_SELECTORS: [FieldElement; 3] = [
+    selector!("get_implementation_hash"),
+    selector!("get_implementation"),
+    selector!("implementation"),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractType {
+    ERC721,
+    ERC1155,
+    ERC20,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum DetectionConfidence {
+    /// Only a subset of the expected entrypoints were found.
+    Low,
+    /// Entrypoints specific to the standard were found.
+    Medium,
+    /// The contract declares the standard through SRC5,
+    /// or its class hash is registered.
+    High,
+}
+
+/// What was observed on the contract during the detection.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DetectionEvidence {
+    SupportsInterface {
+        interface_id: FieldElement,
+        supported: bool,
+    },
+    /// The class hash of the contract is registered with this standard.
+    KnownClassHash { class_hash: FieldElement },
+    /// `owner_of` / `ownerOf` exists.
+    OwnerOf,
+    /// `token_uri` / `tokenURI` exists.
+    TokenUri,
+    /// `balance_of` / `balanceOf` exists and accepts the given number
+    /// of arguments (1 for ERC-20 / ERC-721, 2 for ERC-1155).
+    BalanceOf { args: usize },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ContractTypeDetection {
+    pub contract_type: ContractType,
+    pub confidence: DetectionConfidence,
+    pub evidence: Vec<DetectionEvidence>,
+    /// Layout of the events of the contract, known for registered classes.
+    pub layout: Option<EventLayout>,
+}
+
+/// Detects the standard implemented by the contract at the given address.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `address` - The address of the contract.
+/// * `block` - The block at which the contract is called.
+pub async fn detect_contract_type<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    block: BlockId,
+) -> Result<ContractTypeDetection, StarknetClientError> {
+    let mut evidence = vec![];
+
+    for (interface_id, contract_type) in [
+        (IERC721_ID, ContractType::ERC721),
+        (IERC721_ID_LEGACY, ContractType::ERC721),
+        (IERC1155_ID, ContractType::ERC1155),
+        (IERC1155_ID_LEGACY, ContractType::ERC1155),
+    ] {
+        let supported = match supports_interface(client, address, interface_id, block).await {
+            Ok(supported) => supported,
+            // The contract does not implement SRC5 (or not correctly), go to probing.
+            Err(StarknetClientError::EntrypointNotFound(_))
+            | Err(StarknetClientError::Contract(_))
+            | Err(StarknetClientError::InputTooShort)
+            | Err(StarknetClientError::InputTooLong) => break,
+            Err(e) => return Err(e),
+        };
+
+        evidence.push(DetectionEvidence::SupportsInterface {
+            interface_id,
+            supported,
+        });
+
+        if supported {
+            return Ok(ContractTypeDetection {
+                contract_type,
+                confidence: DetectionConfidence::High,
+                evidence,
+                layout: None,
+            });
+        }
+    }
+
+    // Token id 1 as u256, the token may not exist, we only care
+    // about the entrypoint existence.
+    let token_id = vec![FieldElement::ONE, FieldElement::ZERO];
+
+    let has_owner_of = entrypoint_exists(
+        call_contract_selectors(
+            client,
+            address,
+            &OWNER_OF_SELECTORS,
+            token_id.clone(),
+            block,
+        )
+        .await,
+    )?;
+    if has_owner_of {
+        evidence.push(DetectionEvidence::OwnerOf);
+    }
+
+    let has_token_uri = entrypoint_exists(
+        call_contract_selectors(client, address, &TOKEN_URI_SELECTORS, token_id, block).await,
+    )?;
+    if has_token_uri {
+        evidence.push(DetectionEvidence::TokenUri);
+    }
+
+    let balance_of_args = balance_of_args(client, address, block).await?;
+    if let Some(args) = balance_of_args {
+        evidence.push(DetectionEvidence::BalanceOf { args });
+    }
+
+    let (contract_type, confidence) = match (has_owner_of, has_token_uri, balance_of_args) {
+        (true, _, _) => (ContractType::ERC721, DetectionConfidence::Medium),
+        (false, _, Some(2)) => (ContractType::ERC1155, DetectionConfidence::Medium),
+        (false, true, Some(1)) => (ContractType::ERC721, DetectionConfidence::Low),
+        (false, false, Some(1)) => (ContractType::ERC20, DetectionConfidence::Low),
+        _ => (ContractType::Other, DetectionConfidence::Low),
+    };
+
+    Ok(ContractTypeDetection {
+        contract_type,
+        confidence,
+        evidence,
+        layout: None,
+    })
+}
+
+/// Calls SRC5 `supports_interface` (or the camelCase version).
+/// Returns `EntrypointNotFound` if the contract does not implement SRC5.
+pub async fn supports_interface<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    interface_id: FieldElement,
+    block: BlockId,
+) -> Result<bool, StarknetClientError> {
+    let r = call_contract_selectors(
+        client,
+        address,
+        &SUPPORTS_INTERFACE_SELECTORS,
+        vec![interface_id],
+        block,
+    )
+    .await?;
+
+    Ok(r.first().is_some_and(|v| *v == FieldElement::ONE))
+}
+
+/// Returns true if the contract is a proxy exposing its implementation. Proxies
+/// of the same class may delegate to implementations of different standards.
+pub async fn is_proxy<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    block: BlockId,
+) -> Result<bool, StarknetClientError> {
+    entrypoint_exists(
+        call_contract_selectors(client, address, &IMPLEMENTATION_SELECTORS, vec![], block).await,
+    )
+}
+
+/// Returns the number of arguments accepted by `balance_of`, or `None`
+/// if the entrypoint does not exist.
+async fn balance_of_args<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    address: FieldElement,
+    block: BlockId,
+) -> Result<Option<usize>, StarknetClientError> {
+    // Any account works to probe, the balance is not used.
+    let account = FieldElement::ONE;
+
+    match call_contract_selectors(client, address, &BALANCE_OF_SELECTORS, vec![account], block)
+        .await
+    {
+        Ok(_) => return Ok(Some(1)),
+        Err(StarknetClientError::EntrypointNotFound(_)) => return Ok(None),
+        Err(StarknetClientError::InputTooShort) => (),
+        // Other contract errors means the entrypoint exists.
+        Err(StarknetClientError::Contract(_)) => return Ok(Some(1)),
+        Err(e) => return Err(e),
+    }
+
+    // ERC-1155 `balance_of(account, token_id: u256)`.
+    let calldata = vec![account, FieldElement::ONE, FieldElement::ZERO];
+
+    let exists = entrypoint_exists(
+        call_contract_selectors(client, address, &BALANCE_OF_SELECTORS, calldata, block).await,
+    )?;
+
+    Ok(exists.then_some(2))
+}
+
+/// Returns true if the call result shows that the entrypoint exists,
+/// even if the call itself failed (non-existent token for instance).
+fn entrypoint_exists(
+    result: Result<Vec<FieldElement>, StarknetClientError>,
+) -> Result<bool, StarknetClientError> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(StarknetClientError::EntrypointNotFound(_)) => Ok(false),
+        Err(StarknetClientError::Contract(_))
+        | Err(StarknetClientError::InputTooShort)
+        | Err(StarknetClientError::InputTooLong) => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::providers::ProviderError;
+
+    const ADDRESS: FieldElement = felt!("0x123");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    /// A Cairo 1 ERC-1155 without SRC5: `balance_of(account)` fails to
+    /// deserialize the second param, `balance_of(account, token_id)` works.
+    #[tokio::test]
+    async fn detect_erc1155_from_balance_of_arity() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, selector, calldata, _| {
+                if selector == selector!("balance_of") {
+                    match calldata.len() {
+                        1 => Err(StarknetClientError::InputTooShort),
+                        _ => Ok(vec![FieldElement::ZERO, FieldElement::ZERO]),
+                    }
+                } else {
+                    Err(StarknetClientError::EntrypointNotFound("".to_string()))
+                }
+            });
+
+        let detection = detect_contract_type(&client, ADDRESS, BLOCK).await.unwrap();
+
+        assert_eq!(detection.contract_type, ContractType::ERC1155);
+        assert!(detection
+            .evidence
+            .contains(&DetectionEvidence::BalanceOf { args: 2 }));
+    }
+
+    /// A transport failure must not be taken for an existing entrypoint.
+    #[tokio::test]
+    async fn detect_propagates_provider_errors() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, selector, _, _| {
+                if selector == selector!("owner_of") {
+                    Err(StarknetClientError::Provider(ProviderError::RateLimited))
+                } else {
+                    Err(StarknetClientError::EntrypointNotFound("".to_string()))
+                }
+            });
+
+        let result = detect_contract_type(&client, ADDRESS, BLOCK).await;
+
+        assert!(matches!(result, Err(StarknetClientError::Provider(_))));
+    }
+}
@@ -0,0 +1,392 @@
+//! Discovery of new NFT collections from the contracts deployed in each block.
+//!
+//! Deployed contracts are gathered from the state update of the block and the
+//! receipts of `DEPLOY` / `DEPLOY_ACCOUNT` transactions. The `ContractDeployed`
+//! events of the Universal Deployer Contract (UDC) give the deployer. Any emitter
+//! of `ContractDeployed` is accepted, as several UDC versions are deployed, so an
+//! event is only trusted if its contract and class hash are in the state update.
+//! The standard of each new contract is then detected with `detect_contract_type`.
+use starknet::core::types::{BlockId, EmittedEvent, FieldElement};
+use starknet::macros::selector;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use super::contract_type::{detect_contract_type, is_proxy, ContractType, ContractTypeDetection};
+use super::registry::ClassHashRegistry;
+use crate::starknet_utils::client::{StarknetClient, StarknetClientError};
+
+pub const CONTRACT_DEPLOYED_SELECTOR: FieldElement = selector!("ContractDeployed");
+
+/// A newly deployed ERC-721 or ERC-1155 contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewCollection {
+    pub address: FieldElement,
+    pub class_hash: FieldElement,
+    /// The account which deployed the contract, known for UDC deployments.
+    pub deployer: Option<FieldElement>,
+    pub block_number: u64,
+    pub transaction_hash: Option<FieldElement>,
+    pub detection: ContractTypeDetection,
+}
+
+/// A contract deployed in the block being inspected.
+#[derive(Debug, Default)]
+struct Deployment {
+    class_hash: Option<FieldElement>,
+    deployer: Option<FieldElement>,
+    transaction_hash: Option<FieldElement>,
+}
+
+/// Discovers new collections block after block, and keeps track
+/// of the collections discovered so far.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionDiscovery {
+    /// Block in which each collection was discovered.
+    collections: HashMap<FieldElement, u64>,
+    /// Detections already done, by class hash. Contracts of the same class
+    /// implement the same standard, the detection is only done once.
+    detections: HashMap<FieldElement, ContractTypeDetection>,
+    /// Classes of proxies, whose contracts are always detected as they may
+    /// delegate to implementations of different standards.
+    proxy_classes: HashSet<FieldElement>,
+    /// Known classes, checked before probing the contracts.
+    registry: Option<Arc<ClassHashRegistry>>,
+}
+
+impl CollectionDiscovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the given registry to classify contracts of known classes without probing them.
+    pub fn with_registry(registry: Arc<ClassHashRegistry>) -> Self {
+        Self {
+            registry: Some(registry),
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if the contract was discovered as a collection.
+    pub fn is_collection(&self, address: &FieldElement) -> bool {
+        self.collections.contains_key(address)
+    }
+
+    /// Returns the addresses of all the collections discovered so far.
+    pub fn collections(&self) -> impl Iterator<Item = &FieldElement> {
+        self.collections.keys()
+    }
+
+    /// Forgets the collections discovered from `block_number`, replaced by a
+    /// reorganization. They are discovered again if deployed in the new chain.
+    pub fn forget_from(&mut self, block_number: u64) {
+        self.collections.retain(|_, block| *block < block_number);
+    }
+
+    /// Inspects the contracts deployed in the block and returns the new collections.
+    /// If the block is replaced by a reorg, `forget_from` must be called before
+    /// inspecting the new one.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Starknet client used to fetch the block and call the contracts.
+    /// * `block_number` - The block to inspect.
+    pub async fn discover_block<C: StarknetClient + Sync + ?Sized>(
+        &mut self,
+        client: &C,
+        block_number: u64,
+    ) -> Result<Vec<NewCollection>, StarknetClientError> {
+        let block = BlockId::Number(block_number);
+        let mut deployments: BTreeMap<FieldElement, Deployment> = BTreeMap::new();
+
+        for contract in client.deployed_contracts(block).await? {
+            deployments.entry(contract.address).or_default().class_hash = Some(contract.class_hash);
+        }
+
+        for contract in client.deploy_transactions(block).await? {
+            let deployment = deployments.entry(contract.address).or_default();
+            deployment.class_hash = Some(contract.class_hash);
+            deployment.transaction_hash = contract.transaction_hash;
+        }
+
+        let udc_events = client
+            .fetch_all_block_events(block, Some(vec![vec![CONTRACT_DEPLOYED_SELECTOR]]))
+            .await?;
+
+        for event in udc_events.values().flatten() {
+            let deployed = match decode_contract_deployed(event) {
+                Some(deployed) => deployed,
+                None => continue,
+            };
+
+            // Any contract can emit the event, for a contract deployed earlier.
+            match deployments.get_mut(&deployed.address) {
+                Some(deployment) if deployment.class_hash == Some(deployed.class_hash) => {
+                    deployment.deployer = Some(deployed.deployer);
+                    deployment.transaction_hash = Some(event.transaction_hash);
+                }
+                _ => continue,
+            }
+        }
+
+        let mut new_collections = vec![];
+        for (address, deployment) in deployments {
+            if self.collections.contains_key(&address) {
+                continue;
+            }
+
+            let class_hash = match deployment.class_hash {
+                Some(class_hash) => class_hash,
+                None => client.class_hash_at(address, block).await?,
+            };
+
+            let known = self
+                .registry
+                .as_ref()
+                .and_then(|r| r.detection(&class_hash));
+
+            let detection = match (known, self.detections.get(&class_hash)) {
+                (Some(detection), _) => detection,
+                (None, Some(detection)) => detection.clone(),
+                (None, None) if self.proxy_classes.contains(&class_hash) => {
+                    detect_contract_type(client, address, block).await?
+                }
+                (None, None) => {
+                    let detection = detect_contract_type(client, address, block).await?;
+                    if is_proxy(client, address, block).await? {
+                        self.proxy_classes.insert(class_hash);
+                    } else {
+                        self.detections.insert(class_hash, detection.clone());
+                    }
+                    detection
+                }
+            };
+
+            if !matches!(
+                detection.contract_type,
+                ContractType::ERC721 | ContractType::ERC1155
+            ) {
+                continue;
+            }
+
+            self.collections.insert(address, block_number);
+            new_collections.push(NewCollection {
+                address,
+                class_hash,
+                deployer: deployment.deployer,
+                block_number,
+                transaction_hash: deployment.transaction_hash,
+                detection,
+            });
+        }
+
+        Ok(new_collections)
+    }
+}
+
+/// Fields of a UDC `ContractDeployed` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ContractDeployed {
+    address: FieldElement,
+    deployer: FieldElement,
+    class_hash: FieldElement,
+}
+
+/// Decodes a UDC `ContractDeployed` event,
+/// `[address, deployer, unique, class_hash, calldata..., salt]` in data.
+fn decode_contract_deployed(event: &EmittedEvent) -> Option<ContractDeployed> {
+    if event.keys.len() != 1 || event.data.len() < 4 {
+        return None;
+    }
+
+    Some(ContractDeployed {
+        address: event.data[0],
+        deployer: event.data[1],
+        class_hash: event.data[3],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nft::contract_type::{
+        IERC721_ID, IMPLEMENTATION_SELECTORS, SUPPORTS_INTERFACE_SELECTORS,
+    };
+    use crate::nft::events::EventLayout;
+    use crate::nft::registry::KnownClass;
+    use crate::starknet_utils::client::{DeployedContract, MockStarknetClient};
+    use starknet::macros::felt;
+
+    const COLLECTION: FieldElement = felt!("0xc011");
+    const CLASS_HASH: FieldElement = felt!("0xc1a55");
+    const DEPLOYER: FieldElement = felt!("0xde9");
+    const UDC_TX: FieldElement = felt!("0x7");
+
+    fn client() -> MockStarknetClient {
+        client_with_events(vec![])
+    }
+
+    fn client_with_events(udc_events: Vec<EmittedEvent>) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client.expect_deployed_contracts().returning(|_| {
+            Ok(vec![DeployedContract {
+                address: COLLECTION,
+                class_hash: CLASS_HASH,
+                transaction_hash: None,
+            }])
+        });
+        client
+            .expect_deploy_transactions()
+            .returning(|_| Ok(vec![]));
+        client
+            .expect_fetch_all_block_events()
+            .returning(move |_, _| Ok(HashMap::from([(10, udc_events.clone())])));
+        client
+    }
+
+    fn contract_deployed(address: FieldElement, class_hash: FieldElement) -> EmittedEvent {
+        EmittedEvent {
+            from_address: felt!("0x0bad"),
+            keys: vec![CONTRACT_DEPLOYED_SELECTOR],
+            data: vec![
+                address,
+                DEPLOYER,
+                FieldElement::ZERO,
+                class_hash,
+                felt!("0x5a17"),
+            ],
+            block_hash: None,
+            block_number: Some(10),
+            transaction_hash: UDC_TX,
+        }
+    }
+
+    fn erc721_registry() -> Arc<ClassHashRegistry> {
+        let registry = ClassHashRegistry::new();
+        registry.insert(KnownClass {
+            class_hash: CLASS_HASH,
+            standard: ContractType::ERC721,
+            layout: EventLayout::Cairo1,
+            name: None,
+        });
+        Arc::new(registry)
+    }
+
+    #[tokio::test]
+    async fn contract_deployed_events_must_match_the_state_update() {
+        let other = felt!("0x07e4");
+        let client = client_with_events(vec![
+            // A contract deployed in an earlier block, or of another class.
+            contract_deployed(other, CLASS_HASH),
+            contract_deployed(COLLECTION, felt!("0x1")),
+        ]);
+        let mut discovery = CollectionDiscovery::with_registry(erc721_registry());
+
+        let collections = discovery.discover_block(&client, 10).await.unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].address, COLLECTION);
+        assert_eq!(collections[0].deployer, None);
+        assert_eq!(collections[0].transaction_hash, None);
+        assert!(!discovery.is_collection(&other));
+
+        let client = client_with_events(vec![contract_deployed(COLLECTION, CLASS_HASH)]);
+        let mut discovery = CollectionDiscovery::with_registry(erc721_registry());
+
+        let collections = discovery.discover_block(&client, 10).await.unwrap();
+        assert_eq!(collections[0].deployer, Some(DEPLOYER));
+        assert_eq!(collections[0].transaction_hash, Some(UDC_TX));
+    }
+
+    /// Two contracts of the same class, only the first one supporting ERC-721,
+    /// both exposing an implementation if `proxies`.
+    fn same_class_client(proxies: bool) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client.expect_deployed_contracts().returning(|_| {
+            Ok([COLLECTION, felt!("0xe2c20")]
+                .into_iter()
+                .map(|address| DeployedContract {
+                    address,
+                    class_hash: CLASS_HASH,
+                    transaction_hash: None,
+                })
+                .collect())
+        });
+        client
+            .expect_deploy_transactions()
+            .returning(|_| Ok(vec![]));
+        client
+            .expect_fetch_all_block_events()
+            .returning(|_, _| Ok(HashMap::new()));
+        client
+            .expect_call_contract()
+            .returning(move |address, selector, calldata, _| {
+                if SUPPORTS_INTERFACE_SELECTORS.contains(&selector) {
+                    let erc721 = address == COLLECTION && calldata == vec![IERC721_ID];
+                    Ok(vec![FieldElement::from(erc721 as u8)])
+                } else if proxies && IMPLEMENTATION_SELECTORS.contains(&selector) {
+                    Ok(vec![felt!("0x1a9")])
+                } else {
+                    Err(StarknetClientError::EntrypointNotFound(
+                        "not found".to_string(),
+                    ))
+                }
+            });
+        client
+    }
+
+    #[tokio::test]
+    async fn detections_are_cached_by_class() {
+        let mut discovery = CollectionDiscovery::new();
+
+        let collections = discovery
+            .discover_block(&same_class_client(false), 10)
+            .await
+            .unwrap();
+
+        // The second contract is given the detection of the first one.
+        assert_eq!(collections.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn proxies_are_not_cached_by_class() {
+        let mut discovery = CollectionDiscovery::new();
+
+        let collections = discovery
+            .discover_block(&same_class_client(true), 10)
+            .await
+            .unwrap();
+
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].address, COLLECTION);
+        assert!(discovery.proxy_classes.contains(&CLASS_HASH));
+        assert!(discovery.detections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replaced_collections_are_discovered_again() {
+        let mut discovery = CollectionDiscovery::with_registry(erc721_registry());
+        let client = client();
+
+        let collections = discovery.discover_block(&client, 10).await.unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].address, COLLECTION);
+        assert!(discovery.is_collection(&COLLECTION));
+
+        // Already known.
+        assert!(discovery
+            .discover_block(&client, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        discovery.forget_from(11);
+        assert!(discovery.is_collection(&COLLECTION));
+
+        // Block 10 is replaced, the contract is deployed in the new one.
+        discovery.forget_from(10);
+        assert!(!discovery.is_collection(&COLLECTION));
+        assert_eq!(
+            discovery.discover_block(&client, 10).await.unwrap().len(),
+            1
+        );
+    }
+}
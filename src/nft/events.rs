@@ -0,0 +1,754 @@
+//! Decoding of ERC-721 and ERC-1155 events from `EmittedEvent`.
+//!
+//! Two layouts are supported:
+//! * Cairo 0, where only the selector is in the keys and every field is in the data.
+//! * Cairo 1, where the `#[key]` fields are appended to the keys after the selector,
+//!   and the other fields are in the data.
+//!
+//! For every event except `URI`, the fields are in the same order in both layouts
+//! once keys (without selector) and data are concatenated.
+//!
+//! A Cairo 0 ERC-20 `Transfer` or `Approval` has the exact same layout as a
+//! Cairo 0 ERC-721 one. They are only decoded for contracts known to be ERC-721,
+//! and are [`EventDecodeError::Ambiguous`] otherwise.
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{EmittedEvent, FieldElement};
+use starknet::macros::selector;
+
+use crate::starknet_utils::byte_array::ByteArray;
+use crate::starknet_utils::cairo_string_parser::parse_cairo_string;
+use crate::starknet_utils::{CairoU256, CairoU256Error};
+
+pub const TRANSFER_SELECTOR: FieldElement = selector!("Transfer");
+pub const APPROVAL_SELECTOR: FieldElement = selector!("Approval");
+pub const APPROVAL_FOR_ALL_SELECTOR: FieldElement = selector!("ApprovalForAll");
+pub const TRANSFER_SINGLE_SELECTOR: FieldElement = selector!("TransferSingle");
+pub const TRANSFER_BATCH_SELECTOR: FieldElement = selector!("TransferBatch");
+pub const URI_SELECTOR: FieldElement = selector!("URI");
+
+/// Errors for events that are recognized by their selector but can't be decoded.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+pub enum EventDecodeError {
+    #[error("Invalid fields count for {0} event: {1}")]
+    InvalidLength(&'static str, usize),
+    #[error("Invalid u256 value: {0}")]
+    U256(#[from] CairoU256Error),
+    #[error("Invalid boolean value: {0}")]
+    InvalidBool(String),
+    #[error("Invalid string value")]
+    InvalidString,
+    #[error("Cairo 0 {0} event may be an ERC-20 one, the contract standard is unknown")]
+    Ambiguous(&'static str),
+}
+
+/// Layout of the event, depending on the Cairo version of the contract.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventLayout {
+    Cairo0,
+    Cairo1,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TokenStandard {
+    ERC721,
+    ERC1155,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NftEventKind {
+    Transfer {
+        from: FieldElement,
+        to: FieldElement,
+        token_id: CairoU256,
+    },
+    Approval {
+        owner: FieldElement,
+        approved: FieldElement,
+        token_id: CairoU256,
+    },
+    ApprovalForAll {
+        owner: FieldElement,
+        operator: FieldElement,
+        approved: bool,
+    },
+    TransferSingle {
+        operator: FieldElement,
+        from: FieldElement,
+        to: FieldElement,
+        token_id: CairoU256,
+        amount: CairoU256,
+    },
+    TransferBatch {
+        operator: FieldElement,
+        from: FieldElement,
+        to: FieldElement,
+        token_ids: Vec<CairoU256>,
+        amounts: Vec<CairoU256>,
+    },
+    Uri {
+        token_id: CairoU256,
+        value: String,
+    },
+}
+
+/// A decoded NFT event, with the metadata of the `EmittedEvent` it comes from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NftEvent {
+    pub contract_address: FieldElement,
+    pub transaction_hash: FieldElement,
+    pub block_hash: Option<FieldElement>,
+    pub block_number: Option<u64>,
+    pub layout: EventLayout,
+    pub kind: NftEventKind,
+}
+
+/// A single token transfer, ERC-721 or ERC-1155.
+/// For ERC-721, `amount` is always one and `operator` is `None`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TokenTransfer {
+    pub contract_address: FieldElement,
+    pub transaction_hash: FieldElement,
+    pub block_number: Option<u64>,
+    pub standard: TokenStandard,
+    pub operator: Option<FieldElement>,
+    pub from: FieldElement,
+    pub to: FieldElement,
+    pub token_id: CairoU256,
+    pub amount: CairoU256,
+}
+
+impl NftEvent {
+    /// Returns the token transfers carried by the event. A `TransferBatch`
+    /// is flattened into one transfer per token id, other events that are
+    /// not transfers return an empty vec.
+    pub fn transfers(&self) -> Vec<TokenTransfer> {
+        let transfer = |standard, operator, from, to, token_id, amount| TokenTransfer {
+            contract_address: self.contract_address,
+            transaction_hash: self.transaction_hash,
+            block_number: self.block_number,
+            standard,
+            operator,
+            from,
+            to,
+            token_id,
+            amount,
+        };
+
+        match &self.kind {
+            NftEventKind::Transfer { from, to, token_id } => vec![transfer(
+                TokenStandard::ERC721,
+                None,
+                *from,
+                *to,
+                *token_id,
+                CairoU256::ONE,
+            )],
+            NftEventKind::TransferSingle {
+                operator,
+                from,
+                to,
+                token_id,
+                amount,
+            } => vec![transfer(
+                TokenStandard::ERC1155,
+                Some(*operator),
+                *from,
+                *to,
+                *token_id,
+                *amount,
+            )],
+            NftEventKind::TransferBatch {
+                operator,
+                from,
+                to,
+                token_ids,
+                amounts,
+            } => token_ids
+                .iter()
+                .zip(amounts)
+                .map(|(token_id, amount)| {
+                    transfer(
+                        TokenStandard::ERC1155,
+                        Some(*operator),
+                        *from,
+                        *to,
+                        *token_id,
+                        *amount,
+                    )
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Decodes an NFT event of a contract whose standard is unknown.
+///
+/// Returns `Ok(None)` if the event is not an ERC-721 / ERC-1155 event,
+/// and an error if the selector is recognized but the fields can't be decoded,
+/// or if it is a Cairo 0 `Transfer` or `Approval`, which may be an ERC-20 one.
+///
+/// # Arguments
+///
+/// * `event` - The event, as returned by `fetch_events` or `events_from_tx_receipt`.
+pub fn decode_event(event: &EmittedEvent) -> Result<Option<NftEvent>, EventDecodeError> {
+    decode_contract_event(event, None, None)
+}
+
+/// Decodes an NFT event of a contract whose standard and event layout may be
+/// known, from `detect_contract_type` or the class hash registry for instance.
+///
+/// Returns `Ok(None)` if the event is not an ERC-721 / ERC-1155 event, or
+/// if its layout is not the expected one: a Cairo 1 collection never emits a
+/// `Transfer` without `#[key]` fields, which is then an event of another kind.
+///
+/// # Arguments
+///
+/// * `event` - The event, as returned by `fetch_events` or `events_from_tx_receipt`.
+/// * `standard` - The standard of the contract, if known. Cairo 0 `Transfer` and
+///   `Approval` events are only decoded for ERC-721 contracts.
+/// * `expected` - The layout of the events of the contract, if known.
+pub fn decode_contract_event(
+    event: &EmittedEvent,
+    standard: Option<TokenStandard>,
+    expected: Option<EventLayout>,
+) -> Result<Option<NftEvent>, EventDecodeError> {
+    let selector = match event.keys.first() {
+        Some(s) => *s,
+        None => return Ok(None),
+    };
+
+    let layout = if event.keys.len() > 1 {
+        EventLayout::Cairo1
+    } else {
+        EventLayout::Cairo0
+    };
+
+    if expected.is_some_and(|expected| expected != layout) {
+        return Ok(None);
+    }
+
+    // Number of `#[key]` fields of Cairo 1 events, used to filter out
+    // events of other standards using the same name.
+    let cairo1_keys_len = event.keys.len() - 1;
+
+    let mut fields: Vec<FieldElement> = event.keys[1..].to_vec();
+    fields.extend(event.data.iter().copied());
+
+    // A Cairo 0 ERC-20 event has the same fields as the ERC-721 one.
+    let check_cairo0_standard = |name| match (layout, standard) {
+        (EventLayout::Cairo1, _) | (_, Some(TokenStandard::ERC721)) => Ok(true),
+        (_, Some(TokenStandard::ERC1155)) => Ok(false),
+        (_, None) => Err(EventDecodeError::Ambiguous(name)),
+    };
+
+    let kind = match selector {
+        s if s == TRANSFER_SELECTOR => {
+            if layout == EventLayout::Cairo1 && cairo1_keys_len != fields.len() {
+                // ERC-20 `Transfer` has its amount in the data.
+                return Ok(None);
+            }
+            if !check_cairo0_standard("Transfer")? {
+                return Ok(None);
+            }
+
+            let (from, to, token_id) = match fields.len() {
+                3 => (fields[0], fields[1], CairoU256::from(fields[2])),
+                4 => (fields[0], fields[1], u256_at(&fields, 2)?),
+                len => return Err(EventDecodeError::InvalidLength("Transfer", len)),
+            };
+
+            NftEventKind::Transfer { from, to, token_id }
+        }
+        s if s == APPROVAL_SELECTOR => {
+            if layout == EventLayout::Cairo1 && cairo1_keys_len != fields.len() {
+                // ERC-20 `Approval` has its amount in the data.
+                return Ok(None);
+            }
+            if !check_cairo0_standard("Approval")? {
+                return Ok(None);
+            }
+
+            let (owner, approved, token_id) = match fields.len() {
+                3 => (fields[0], fields[1], CairoU256::from(fields[2])),
+                4 => (fields[0], fields[1], u256_at(&fields, 2)?),
+                len => return Err(EventDecodeError::InvalidLength("Approval", len)),
+            };
+
+            NftEventKind::Approval {
+                owner,
+                approved,
+                token_id,
+            }
+        }
+        s if s == APPROVAL_FOR_ALL_SELECTOR => {
+            if fields.len() != 3 {
+                return Err(EventDecodeError::InvalidLength(
+                    "ApprovalForAll",
+                    fields.len(),
+                ));
+            }
+
+            NftEventKind::ApprovalForAll {
+                owner: fields[0],
+                operator: fields[1],
+                approved: felt_to_bool(&fields[2])?,
+            }
+        }
+        s if s == TRANSFER_SINGLE_SELECTOR => {
+            if fields.len() != 7 {
+                return Err(EventDecodeError::InvalidLength(
+                    "TransferSingle",
+                    fields.len(),
+                ));
+            }
+
+            NftEventKind::TransferSingle {
+                operator: fields[0],
+                from: fields[1],
+                to: fields[2],
+                token_id: u256_at(&fields, 3)?,
+                amount: u256_at(&fields, 5)?,
+            }
+        }
+        s if s == TRANSFER_BATCH_SELECTOR => decode_transfer_batch(&fields)?,
+        s if s == URI_SELECTOR => decode_uri(&fields, layout)?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(NftEvent {
+        contract_address: event.from_address,
+        transaction_hash: event.transaction_hash,
+        block_hash: event.block_hash,
+        block_number: event.block_number,
+        layout,
+        kind,
+    }))
+}
+
+/// Decodes all the NFT events of the given list, skipping the events
+/// that are not NFT events, that can't be decoded, or that are ambiguous
+/// as the standard of their contract is unknown.
+pub fn decode_events(events: &[EmittedEvent]) -> Vec<NftEvent> {
+    events
+        .iter()
+        .filter_map(|e| decode_event(e).ok().flatten())
+        .collect()
+}
+
+/// `[operator, from, to, ids_len, ids..., amounts_len, amounts...]`,
+/// ids and amounts being `u256`.
+fn decode_transfer_batch(fields: &[FieldElement]) -> Result<NftEventKind, EventDecodeError> {
+    let invalid_length = EventDecodeError::InvalidLength("TransferBatch", fields.len());
+
+    let (token_ids, ids_felts) = u256_array_at(fields, 3).ok_or(invalid_length.clone())?;
+    let (amounts, amounts_felts) =
+        u256_array_at(fields, 3 + ids_felts).ok_or(invalid_length.clone())?;
+
+    if token_ids.len() != amounts.len() || fields.len() != 3 + ids_felts + amounts_felts {
+        return Err(invalid_length);
+    }
+
+    Ok(NftEventKind::TransferBatch {
+        operator: fields[0],
+        from: fields[1],
+        to: fields[2],
+        token_ids,
+        amounts,
+    })
+}
+
+/// Cairo 0: `[value..., id_low, id_high]`, value being a short string or a legacy long string.
+/// Cairo 1: `[id_low, id_high, value...]`, value being a `ByteArray`.
+fn decode_uri(
+    fields: &[FieldElement],
+    layout: EventLayout,
+) -> Result<NftEventKind, EventDecodeError> {
+    if fields.len() < 3 {
+        return Err(EventDecodeError::InvalidLength("URI", fields.len()));
+    }
+
+    let (token_id, value) = match layout {
+        EventLayout::Cairo0 => {
+            let id_index = fields.len() - 2;
+            let value = parse_cairo_string(fields[..id_index].to_vec())
+                .map_err(|_| EventDecodeError::InvalidString)?;
+
+            (u256_at(fields, id_index)?, value)
+        }
+        EventLayout::Cairo1 => {
+            let (byte_array, consumed) =
+                ByteArray::from_felts(&fields[2..]).map_err(|_| EventDecodeError::InvalidString)?;
+
+            if consumed != fields.len() - 2 {
+                return Err(EventDecodeError::InvalidLength("URI", fields.len()));
+            }
+
+            (u256_at(fields, 0)?, byte_array.to_string_lossy())
+        }
+    };
+
+    Ok(NftEventKind::Uri { token_id, value })
+}
+
+/// Reads a `u256` serialized as `[low, high]` at the given index.
+fn u256_at(fields: &[FieldElement], index: usize) -> Result<CairoU256, EventDecodeError> {
+    Ok(CairoU256::from_felts(fields[index], fields[index + 1])?)
+}
+
+/// Reads an array of `u256` serialized as `[len, low, high, ...]` at the given index.
+/// Returns the values with the number of felts consumed, or `None` if the fields
+/// are too short or a value is not a valid `u256`.
+fn u256_array_at(fields: &[FieldElement], index: usize) -> Option<(Vec<CairoU256>, usize)> {
+    let len = usize::try_from(u64::try_from(*fields.get(index)?).ok()?).ok()?;
+    let consumed = len.checked_mul(2)?.checked_add(1)?;
+
+    if index.checked_add(consumed)? > fields.len() {
+        return None;
+    }
+
+    let values = (0..len)
+        .map(|i| u256_at(fields, index + 1 + i * 2).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((values, consumed))
+}
+
+fn felt_to_bool(felt: &FieldElement) -> Result<bool, EventDecodeError> {
+    if *felt == FieldElement::ZERO {
+        Ok(false)
+    } else if *felt == FieldElement::ONE {
+        Ok(true)
+    } else {
+        Err(EventDecodeError::InvalidBool(felt.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::core::utils::cairo_short_string_to_felt;
+    use starknet::macros::felt;
+
+    const CONTRACT: FieldElement = felt!("0xc011");
+    const FROM: FieldElement = felt!("0xf");
+    const TO: FieldElement = felt!("0x7");
+    const OPERATOR: FieldElement = felt!("0x0b");
+
+    fn event(keys: Vec<FieldElement>, data: Vec<FieldElement>) -> EmittedEvent {
+        EmittedEvent {
+            from_address: CONTRACT,
+            keys,
+            data,
+            block_hash: None,
+            block_number: Some(12),
+            transaction_hash: felt!("0x1"),
+        }
+    }
+
+    fn felts(values: &[u64]) -> Vec<FieldElement> {
+        values.iter().map(|v| FieldElement::from(*v)).collect()
+    }
+
+    fn kind(event: &EmittedEvent) -> NftEventKind {
+        decode_contract_event(event, Some(TokenStandard::ERC721), None)
+            .unwrap()
+            .unwrap()
+            .kind
+    }
+
+    #[test]
+    fn cairo0_transfer_needs_the_standard() {
+        let transfer = event(
+            vec![TRANSFER_SELECTOR],
+            vec![FROM, TO, felt!("0x5"), felt!("0x1")],
+        );
+
+        assert_eq!(
+            decode_event(&transfer),
+            Err(EventDecodeError::Ambiguous("Transfer"))
+        );
+        assert_eq!(
+            decode_contract_event(&transfer, Some(TokenStandard::ERC1155), None),
+            Ok(None)
+        );
+
+        let decoded = decode_contract_event(&transfer, Some(TokenStandard::ERC721), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded.layout, EventLayout::Cairo0);
+        assert_eq!(
+            decoded.kind,
+            NftEventKind::Transfer {
+                from: FROM,
+                to: TO,
+                token_id: CairoU256::new(5, 1),
+            }
+        );
+
+        // Token id as a single felt.
+        let transfer = event(vec![TRANSFER_SELECTOR], vec![FROM, TO, felt!("0x5")]);
+        assert_eq!(
+            kind(&transfer),
+            NftEventKind::Transfer {
+                from: FROM,
+                to: TO,
+                token_id: CairoU256::from(5_u64),
+            }
+        );
+
+        let approval = event(
+            vec![APPROVAL_SELECTOR],
+            vec![FROM, TO, felt!("0x5"), felt!("0x0")],
+        );
+        assert_eq!(
+            decode_event(&approval),
+            Err(EventDecodeError::Ambiguous("Approval"))
+        );
+        assert_eq!(
+            kind(&approval),
+            NftEventKind::Approval {
+                owner: FROM,
+                approved: TO,
+                token_id: CairoU256::from(5_u64),
+            }
+        );
+    }
+
+    #[test]
+    fn cairo1_transfer_has_its_fields_in_the_keys() {
+        let transfer = event(
+            vec![TRANSFER_SELECTOR, FROM, TO, felt!("0x5"), felt!("0x0")],
+            vec![],
+        );
+
+        let decoded = decode_event(&transfer).unwrap().unwrap();
+        assert_eq!(decoded.layout, EventLayout::Cairo1);
+        assert_eq!(decoded.contract_address, CONTRACT);
+        assert_eq!(decoded.block_number, Some(12));
+        assert_eq!(
+            decoded.kind,
+            NftEventKind::Transfer {
+                from: FROM,
+                to: TO,
+                token_id: CairoU256::from(5_u64),
+            }
+        );
+        assert_eq!(decoded.transfers()[0].amount, CairoU256::ONE);
+    }
+
+    #[test]
+    fn cairo1_erc20_events_are_skipped() {
+        let transfer = event(
+            vec![TRANSFER_SELECTOR, FROM, TO],
+            vec![felt!("0x5"), felt!("0x0")],
+        );
+        assert_eq!(decode_event(&transfer), Ok(None));
+
+        let approval = event(
+            vec![APPROVAL_SELECTOR, FROM, TO],
+            vec![felt!("0x5"), felt!("0x0")],
+        );
+        assert_eq!(decode_event(&approval), Ok(None));
+    }
+
+    #[test]
+    fn expected_layout() {
+        let cairo0 = event(
+            vec![TRANSFER_SELECTOR],
+            vec![FROM, TO, felt!("0x5"), felt!("0x0")],
+        );
+        let standard = Some(TokenStandard::ERC721);
+
+        assert_eq!(
+            decode_contract_event(&cairo0, standard, Some(EventLayout::Cairo1)),
+            Ok(None)
+        );
+        assert!(
+            decode_contract_event(&cairo0, standard, Some(EventLayout::Cairo0))
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn invalid_fields() {
+        let transfer = event(vec![TRANSFER_SELECTOR], vec![FROM, TO]);
+        assert_eq!(
+            decode_contract_event(&transfer, Some(TokenStandard::ERC721), None),
+            Err(EventDecodeError::InvalidLength("Transfer", 2))
+        );
+
+        let approval_for_all = event(
+            vec![APPROVAL_FOR_ALL_SELECTOR],
+            vec![FROM, OPERATOR, felt!("0x2")],
+        );
+        assert_eq!(
+            decode_event(&approval_for_all),
+            Err(EventDecodeError::InvalidBool("2".to_string()))
+        );
+
+        assert_eq!(decode_event(&event(vec![], vec![FROM])), Ok(None));
+        assert_eq!(
+            decode_event(&event(vec![selector!("Other")], vec![FROM])),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn approval_for_all() {
+        for keys in [
+            vec![APPROVAL_FOR_ALL_SELECTOR],
+            vec![APPROVAL_FOR_ALL_SELECTOR, FROM, OPERATOR],
+        ] {
+            let data = if keys.len() == 1 {
+                vec![FROM, OPERATOR, FieldElement::ONE]
+            } else {
+                vec![FieldElement::ONE]
+            };
+
+            assert_eq!(
+                decode_event(&event(keys, data)).unwrap().unwrap().kind,
+                NftEventKind::ApprovalForAll {
+                    owner: FROM,
+                    operator: OPERATOR,
+                    approved: true,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn transfer_single_in_both_layouts() {
+        let expected = NftEventKind::TransferSingle {
+            operator: OPERATOR,
+            from: FROM,
+            to: TO,
+            token_id: CairoU256::from(3_u64),
+            amount: CairoU256::from(10_u64),
+        };
+        let amounts = felts(&[3, 0, 10, 0]);
+
+        let mut data = vec![OPERATOR, FROM, TO];
+        data.extend(&amounts);
+        let cairo0 = event(vec![TRANSFER_SINGLE_SELECTOR], data);
+        assert_eq!(decode_event(&cairo0).unwrap().unwrap().kind, expected);
+
+        let cairo1 = event(vec![TRANSFER_SINGLE_SELECTOR, OPERATOR, FROM, TO], amounts);
+        let decoded = decode_event(&cairo1).unwrap().unwrap();
+        assert_eq!(decoded.layout, EventLayout::Cairo1);
+        assert_eq!(decoded.kind, expected);
+
+        let transfers = decoded.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].standard, TokenStandard::ERC1155);
+        assert_eq!(transfers[0].operator, Some(OPERATOR));
+        assert_eq!(transfers[0].amount, CairoU256::from(10_u64));
+    }
+
+    #[test]
+    fn transfer_batch_is_flattened() {
+        let mut data = vec![OPERATOR, FROM, TO];
+        data.extend(felts(&[2, 1, 0, 2, 0, 2, 5, 0, 6, 0]));
+
+        let decoded = decode_event(&event(vec![TRANSFER_BATCH_SELECTOR], data.clone()))
+            .unwrap()
+            .unwrap();
+        let transfers = decoded.transfers();
+
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].token_id, CairoU256::from(1_u64));
+        assert_eq!(transfers[0].amount, CairoU256::from(5_u64));
+        assert_eq!(transfers[1].token_id, CairoU256::from(2_u64));
+        assert_eq!(transfers[1].amount, CairoU256::from(6_u64));
+        assert!(transfers.iter().all(|t| t.from == FROM && t.to == TO));
+
+        // Cairo 1, with the operator, from and to in the keys.
+        let cairo1 = event(
+            vec![TRANSFER_BATCH_SELECTOR, OPERATOR, FROM, TO],
+            data[3..].to_vec(),
+        );
+        assert_eq!(decode_event(&cairo1).unwrap().unwrap().kind, decoded.kind);
+
+        // Amounts missing, or trailing felts.
+        let short = event(
+            vec![TRANSFER_BATCH_SELECTOR],
+            data[..data.len() - 2].to_vec(),
+        );
+        assert!(matches!(
+            decode_event(&short),
+            Err(EventDecodeError::InvalidLength("TransferBatch", _))
+        ));
+        let mut long = data.clone();
+        long.push(FieldElement::ZERO);
+        assert!(decode_event(&event(vec![TRANSFER_BATCH_SELECTOR], long)).is_err());
+
+        // Lengths of ids and amounts differ.
+        let mut mismatch = vec![OPERATOR, FROM, TO];
+        mismatch.extend(felts(&[1, 1, 0, 0]));
+        assert!(decode_event(&event(vec![TRANSFER_BATCH_SELECTOR], mismatch)).is_err());
+    }
+
+    #[test]
+    fn uri_in_both_layouts() {
+        let uri = "ipfs://bafy/1.json";
+
+        // Cairo 0: value as a short string, then the token id.
+        let value = cairo_short_string_to_felt(uri).unwrap();
+        let cairo0 = event(vec![URI_SELECTOR], vec![value, felt!("0x1"), felt!("0x0")]);
+        assert_eq!(
+            decode_event(&cairo0).unwrap().unwrap().kind,
+            NftEventKind::Uri {
+                token_id: CairoU256::ONE,
+                value: uri.to_string(),
+            }
+        );
+
+        // Cairo 1: token id as a key, value as a `ByteArray`.
+        let cairo1 = event(
+            vec![URI_SELECTOR, felt!("0x1"), felt!("0x0")],
+            ByteArray::from_string(uri).to_felts(),
+        );
+        let decoded = decode_event(&cairo1).unwrap().unwrap();
+        assert_eq!(decoded.layout, EventLayout::Cairo1);
+        assert_eq!(
+            decoded.kind,
+            NftEventKind::Uri {
+                token_id: CairoU256::ONE,
+                value: uri.to_string(),
+            }
+        );
+        assert!(decoded.transfers().is_empty());
+
+        let mut trailing = ByteArray::from_string(uri).to_felts();
+        trailing.push(FieldElement::ZERO);
+        let cairo1 = event(vec![URI_SELECTOR, felt!("0x1"), felt!("0x0")], trailing);
+        assert!(decode_event(&cairo1).is_err());
+
+        assert_eq!(
+            decode_event(&event(vec![URI_SELECTOR], vec![value])),
+            Err(EventDecodeError::InvalidLength("URI", 1))
+        );
+    }
+
+    #[test]
+    fn decode_events_skips_ambiguous_and_invalid_events() {
+        let events = [
+            event(
+                vec![TRANSFER_SELECTOR],
+                vec![FROM, TO, felt!("0x5"), felt!("0x0")],
+            ),
+            event(
+                vec![TRANSFER_SELECTOR, FROM, TO, felt!("0x5"), felt!("0x0")],
+                vec![],
+            ),
+            event(vec![TRANSFER_SINGLE_SELECTOR], vec![OPERATOR]),
+            event(vec![selector!("Other")], vec![]),
+        ];
+
+        let decoded = decode_events(&events);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].layout, EventLayout::Cairo1);
+    }
+}
@@ -0,0 +1,394 @@
+//! Metadata fetcher using HTTP, with IPFS and Arweave gateways rewriting.
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+use super::{mime_type, FetchedContent, MetadataFetchError, MetadataFetcher};
+
+const IPFS_SCHEME: &str = "ipfs://";
+const ARWEAVE_SCHEME: &str = "ar://";
+
+#[derive(Debug, Clone)]
+pub struct HttpFetcherConfig {
+    /// Gateway used for `ipfs://` URIs, the CID and path are appended to it.
+    pub ipfs_gateway: String,
+    /// Gateway used for `ar://` URIs, the transaction id and path are appended to it.
+    pub arweave_gateway: String,
+    /// Timeout for the whole request, including the body download.
+    pub timeout: Duration,
+    /// Maximum size of the content, in bytes.
+    pub max_size: usize,
+    /// Accepted mime types. If empty, any content type is accepted.
+    /// Responses without content type are accepted if their content is
+    /// recognized as one of them, see `sniff_mime_type`.
+    pub allowed_content_types: Vec<String>,
+}
+
+impl Default for HttpFetcherConfig {
+    fn default() -> Self {
+        Self {
+            ipfs_gateway: "https://ipfs.io/ipfs/".to_string(),
+            arweave_gateway: "https://arweave.net/".to_string(),
+            timeout: Duration::from_secs(10),
+            max_size: 5 * 1024 * 1024,
+            allowed_content_types: vec![
+                "application/json".to_string(),
+                "text/plain".to_string(),
+                "application/octet-stream".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpMetadataFetcher {
+    client: reqwest::Client,
+    config: HttpFetcherConfig,
+}
+
+impl HttpMetadataFetcher {
+    pub fn new(config: HttpFetcherConfig) -> Result<Self, MetadataFetchError> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| MetadataFetchError::Request(e.to_string()))?;
+
+        Ok(Self { client, config })
+    }
+
+    /// Rewrites `ipfs://` and `ar://` URIs to their HTTP gateway equivalent.
+    /// Returns an error for URIs that can't be fetched over HTTP.
+    pub fn http_url(&self, uri: &str) -> Result<String, MetadataFetchError> {
+        if let Some(path) = uri.strip_prefix(IPFS_SCHEME) {
+            // `ipfs://ipfs/<cid>` is a common mistake.
+            let path = path.strip_prefix("ipfs/").unwrap_or(path);
+            Ok(join_url(&self.config.ipfs_gateway, path))
+        } else if let Some(path) = uri.strip_prefix(ARWEAVE_SCHEME) {
+            Ok(join_url(&self.config.arweave_gateway, path))
+        } else if uri.starts_with("https://") || uri.starts_with("http://") {
+            Ok(uri.to_string())
+        } else {
+            Err(MetadataFetchError::UnsupportedUri(uri.to_string()))
+        }
+    }
+
+    /// Returns an error if the mime type is not allowed.
+    fn check_content_type(&self, content_type: &str) -> Result<(), MetadataFetchError> {
+        let allowed = &self.config.allowed_content_types;
+        if !allowed.is_empty() && !allowed.iter().any(|a| a == content_type) {
+            return Err(MetadataFetchError::UnexpectedContentType(
+                content_type.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MetadataFetcher for HttpMetadataFetcher {
+    async fn fetch(&self, uri: &str) -> Result<FetchedContent, MetadataFetchError> {
+        let url = self.http_url(uri)?;
+
+        let mut response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| request_error(e, &url))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => return Err(MetadataFetchError::NotFound(url)),
+            s if !s.is_success() => return Err(MetadataFetchError::Status(s.as_u16())),
+            _ => (),
+        };
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(mime_type);
+
+        if let Some(content_type) = &content_type {
+            self.check_content_type(content_type)?;
+        }
+
+        let max_size = self.config.max_size;
+
+        if response
+            .content_length()
+            .is_some_and(|len| len > max_size as u64)
+        {
+            return Err(MetadataFetchError::TooLarge(max_size));
+        }
+
+        // The content length may be missing or wrong, the size is also
+        // checked while downloading the body.
+        let mut data = vec![];
+        while let Some(chunk) = response.chunk().await.map_err(|e| request_error(e, &url))? {
+            if data.len() + chunk.len() > max_size {
+                return Err(MetadataFetchError::TooLarge(max_size));
+            }
+
+            data.extend_from_slice(&chunk);
+        }
+
+        if content_type.is_none() && !self.config.allowed_content_types.is_empty() {
+            match sniff_mime_type(&data) {
+                Some(sniffed) => self.check_content_type(sniffed)?,
+                None => {
+                    return Err(MetadataFetchError::UnexpectedContentType(
+                        "none".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(FetchedContent { content_type, data })
+    }
+}
+
+/// Guesses the mime type of a content served without `Content-Type`.
+/// Only JSON is recognized, from its first character.
+fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') | Some(b'[') => Some("application/json"),
+        _ => None,
+    }
+}
+
+fn request_error(error: reqwest::Error, url: &str) -> MetadataFetchError {
+    if error.is_timeout() {
+        MetadataFetchError::Timeout(url.to_string())
+    } else {
+        MetadataFetchError::Request(error.to_string())
+    }
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    const JSON: &str = r#"{"name":"Token #1"}"#;
+
+    fn response(status: u16, content_type: &str, body: impl Into<Body>) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .header("content-type", content_type)
+            .body(body.into())
+            .unwrap()
+    }
+
+    fn untyped(body: &'static str) -> Response<Body> {
+        Response::new(Body::from(body))
+    }
+
+    fn redirect(location: &str) -> Response<Body> {
+        Response::builder()
+            .status(302)
+            .header("location", location)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        Ok(match request.uri().path() {
+            "/token.json" => response(200, "application/json; charset=utf-8", JSON),
+            "/slow" => {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                response(200, "application/json", JSON)
+            }
+            "/large" => response(200, "application/json", vec![b' '; 64]),
+            // Sent by chunks without content length.
+            "/large-stream" => {
+                let (mut sender, body) = Body::channel();
+                tokio::spawn(async move {
+                    for _ in 0..8 {
+                        if sender.send_data(vec![b' '; 8].into()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                response(200, "application/json", body)
+            }
+            "/page.html" => response(200, "text/html", "<html></html>"),
+            "/untyped.json" => untyped(JSON),
+            "/untyped.html" => untyped("<html></html>"),
+            "/redirect" => redirect("/token.json"),
+            "/loop" => redirect("/loop"),
+            _ => response(404, "text/plain", "Not found"),
+        })
+    }
+
+    /// Serves `handle` on a local port until the test ends.
+    fn serve() -> SocketAddr {
+        let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+        address
+    }
+
+    fn fetcher() -> HttpMetadataFetcher {
+        HttpMetadataFetcher::new(HttpFetcherConfig {
+            timeout: Duration::from_millis(500),
+            max_size: 32,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetch_json() {
+        let address = serve();
+
+        let content = fetcher()
+            .fetch(&format!("http://{}/token.json", address))
+            .await
+            .unwrap();
+
+        assert_eq!(content.content_type.as_deref(), Some("application/json"));
+        assert_eq!(content.data, JSON.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let address = serve();
+        let url = format!("http://{}/slow", address);
+
+        let result = fetcher().fetch(&url).await;
+
+        assert!(
+            matches!(&result, Err(MetadataFetchError::Timeout(u)) if *u == url),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn size_limit() {
+        let address = serve();
+
+        for path in ["large", "large-stream"] {
+            let result = fetcher()
+                .fetch(&format!("http://{}/{}", address, path))
+                .await;
+
+            assert!(
+                matches!(result, Err(MetadataFetchError::TooLarge(32))),
+                "{}: {:?}",
+                path,
+                result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_content_type() {
+        let address = serve();
+
+        let result = fetcher()
+            .fetch(&format!("http://{}/page.html", address))
+            .await;
+
+        assert!(
+            matches!(&result, Err(MetadataFetchError::UnexpectedContentType(t)) if t == "text/html"),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_content_type() {
+        let address = serve();
+
+        let content = fetcher()
+            .fetch(&format!("http://{}/untyped.json", address))
+            .await
+            .unwrap();
+        assert_eq!(content.content_type, None);
+        assert_eq!(content.data, JSON.as_bytes());
+
+        let result = fetcher()
+            .fetch(&format!("http://{}/untyped.html", address))
+            .await;
+        assert!(
+            matches!(&result, Err(MetadataFetchError::UnexpectedContentType(t)) if t == "none"),
+            "{:?}",
+            result
+        );
+
+        // JSON is not accepted if it's not allowed.
+        let fetcher = HttpMetadataFetcher::new(HttpFetcherConfig {
+            allowed_content_types: vec!["image/png".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let result = fetcher
+            .fetch(&format!("http://{}/untyped.json", address))
+            .await;
+        assert!(
+            matches!(&result, Err(MetadataFetchError::UnexpectedContentType(t)) if t == "application/json"),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn sniffing() {
+        assert_eq!(sniff_mime_type(b" \n{}"), Some("application/json"));
+        assert_eq!(
+            sniff_mime_type(b"\xef\xbb\xbf[1]"),
+            Some("application/json")
+        );
+        assert_eq!(sniff_mime_type(b"<svg/>"), None);
+        assert_eq!(sniff_mime_type(b""), None);
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let address = serve();
+        let url = format!("http://{}/missing.json", address);
+
+        let result = fetcher().fetch(&url).await;
+
+        assert!(
+            matches!(&result, Err(MetadataFetchError::NotFound(u)) if *u == url),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn redirects() {
+        let address = serve();
+
+        let content = fetcher()
+            .fetch(&format!("http://{}/redirect", address))
+            .await
+            .unwrap();
+        assert_eq!(content.data, JSON.as_bytes());
+
+        // Redirect loops are stopped by the redirect limit of the client.
+        let result = fetcher().fetch(&format!("http://{}/loop", address)).await;
+        assert!(
+            matches!(result, Err(MetadataFetchError::Request(_))),
+            "{:?}",
+            result
+        );
+    }
+}
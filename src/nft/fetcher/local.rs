@@ -0,0 +1,175 @@
+//! Metadata fetchers that don't use the network, for tests and
+//! for metadata mirrored locally.
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use super::{FetchedContent, MetadataFetchError, MetadataFetcher};
+
+/// Fetcher serving content registered in memory, by exact URI.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMetadataFetcher {
+    pub entries: HashMap<String, FetchedContent>,
+}
+
+impl InMemoryMetadataFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers JSON content for the given URI.
+    pub fn insert_json(&mut self, uri: &str, json: &str) {
+        self.entries.insert(
+            uri.to_string(),
+            FetchedContent {
+                content_type: Some("application/json".to_string()),
+                data: json.as_bytes().to_vec(),
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl MetadataFetcher for InMemoryMetadataFetcher {
+    async fn fetch(&self, uri: &str) -> Result<FetchedContent, MetadataFetchError> {
+        self.entries
+            .get(uri)
+            .cloned()
+            .ok_or_else(|| MetadataFetchError::NotFound(uri.to_string()))
+    }
+}
+
+/// Fetcher reading content from a directory, where the URI without
+/// its scheme is the relative path of the file:
+/// * `ipfs://<cid>/1.json` is read from `<root>/<cid>/1.json`.
+/// * `https://example.com/meta/1` is read from `<root>/example.com/meta/1`.
+#[derive(Debug, Clone)]
+pub struct FileSystemMetadataFetcher {
+    pub root: PathBuf,
+}
+
+impl FileSystemMetadataFetcher {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the path of the file for the given URI. Returns an error
+    /// if the URI has no scheme or path, or tries to escape the root directory.
+    pub fn path(&self, uri: &str) -> Result<PathBuf, MetadataFetchError> {
+        let (_, relative) = uri
+            .split_once("://")
+            .ok_or_else(|| MetadataFetchError::UnsupportedUri(uri.to_string()))?;
+
+        // Query and fragment are not part of the path.
+        let relative = relative.split(['?', '#']).next().unwrap_or_default();
+        let relative = Path::new(relative);
+
+        if relative.as_os_str().is_empty()
+            || relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(MetadataFetchError::UnsupportedUri(uri.to_string()));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl MetadataFetcher for FileSystemMetadataFetcher {
+    async fn fetch(&self, uri: &str) -> Result<FetchedContent, MetadataFetchError> {
+        let path = self.path(uri)?;
+
+        let data = tokio::fs::read(&path).await.map_err(|e| match e.kind() {
+            ErrorKind::NotFound => MetadataFetchError::NotFound(uri.to_string()),
+            _ => MetadataFetchError::Io(e),
+        })?;
+
+        Ok(FetchedContent {
+            content_type: None,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"name":"Token #1"}"#;
+
+    #[tokio::test]
+    async fn in_memory() {
+        let mut fetcher = InMemoryMetadataFetcher::new();
+        fetcher.insert_json("ipfs://bafy/1.json", JSON);
+
+        let content = fetcher.fetch("ipfs://bafy/1.json").await.unwrap();
+        assert_eq!(content.content_type.as_deref(), Some("application/json"));
+        assert_eq!(content.data, JSON.as_bytes());
+
+        assert!(matches!(
+            fetcher.fetch("ipfs://bafy/2.json").await,
+            Err(MetadataFetchError::NotFound(u)) if u == "ipfs://bafy/2.json"
+        ));
+    }
+
+    #[test]
+    fn path_of_uri() {
+        let fetcher = FileSystemMetadataFetcher::new("/mirror");
+
+        assert_eq!(
+            fetcher.path("ipfs://bafy/1.json").unwrap(),
+            Path::new("/mirror/bafy/1.json")
+        );
+        assert_eq!(
+            fetcher.path("https://example.com/meta/1?v=2#name").unwrap(),
+            Path::new("/mirror/example.com/meta/1")
+        );
+    }
+
+    #[test]
+    fn path_can_not_escape_the_root() {
+        let fetcher = FileSystemMetadataFetcher::new("/mirror");
+
+        for uri in [
+            "ipfs://../secret.json",
+            "https://example.com/../../etc/passwd",
+            "ar://tx/..",
+            "file:///etc/passwd",
+            "ipfs://",
+            "bafy/1.json",
+        ] {
+            assert!(
+                matches!(fetcher.path(uri), Err(MetadataFetchError::UnsupportedUri(u)) if u == uri),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn file_system() {
+        let root = std::env::temp_dir().join(format!("metadata-mirror-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("bafy")).unwrap();
+        std::fs::write(root.join("bafy").join("1.json"), JSON).unwrap();
+        let fetcher = FileSystemMetadataFetcher::new(&root);
+
+        let content = fetcher.fetch("ipfs://bafy/1.json").await.unwrap();
+        assert_eq!(content.content_type, None);
+        assert_eq!(content.data, JSON.as_bytes());
+
+        assert!(matches!(
+            fetcher.fetch("ipfs://bafy/2.json").await,
+            Err(MetadataFetchError::NotFound(_))
+        ));
+        // A directory can't be read.
+        assert!(matches!(
+            fetcher.fetch("ipfs://bafy").await,
+            Err(MetadataFetchError::Io(_))
+        ));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+pub mod http;
+pub mod local;
+use async_trait::async_trait;
+#[cfg(any(test, feature = "mock"))]
+use mockall::automock;
+use starknet::core::types::{BlockId, FieldElement};
+
+use super::metadata::{decode_token_metadata, is_data_uri, MetadataError, TokenMetadata};
+use super::token_uri::{fetch_token_uri, TokenUriError};
+use crate::starknet_utils::client::StarknetClient;
+use crate::starknet_utils::CairoU256;
+
+/// Errors for off-chain metadata fetching.
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataFetchError {
+    #[error("Unsupported URI: {0}")]
+    UnsupportedUri(String),
+    #[error("Content not found: {0}")]
+    NotFound(String),
+    #[error("HTTP request failed: {0}")]
+    Request(String),
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+    #[error("Unexpected HTTP status: {0}")]
+    Status(u16),
+    #[error("Content exceeds the maximum size of {0} bytes")]
+    TooLarge(usize),
+    #[error("Unexpected content type: {0}")]
+    UnexpectedContentType(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid metadata: {0}")]
+    Metadata(#[from] MetadataError),
+    #[error("Can't fetch token uri: {0}")]
+    TokenUri(#[from] TokenUriError),
+}
+
+/// Raw content fetched for a URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedContent {
+    /// Mime type of the content if known, without parameters.
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Fetcher of the content behind off-chain token URIs (`ipfs://`, `https://`...).
+#[cfg_attr(any(test, feature = "mock"), automock)]
+#[async_trait]
+pub trait MetadataFetcher {
+    /// Fetches the raw content at the given URI.
+    async fn fetch(&self, uri: &str) -> Result<FetchedContent, MetadataFetchError>;
+}
+
+/// Resolves a token URI into `TokenMetadata`. Data URIs and raw JSON are decoded
+/// offline, other URIs are fetched with the given fetcher.
+///
+/// # Arguments
+///
+/// * `fetcher` - The fetcher used for off-chain URIs.
+/// * `uri` - The token URI, as returned by `fetch_token_uri`.
+pub async fn resolve_token_metadata<F: MetadataFetcher + Sync + ?Sized>(
+    fetcher: &F,
+    uri: &str,
+) -> Result<TokenMetadata, MetadataFetchError> {
+    let uri = uri.trim();
+
+    if is_data_uri(uri) || uri.starts_with('{') {
+        return Ok(decode_token_metadata(uri)?);
+    }
+
+    let content = fetcher.fetch(uri).await?;
+
+    Ok(TokenMetadata::from_json(&content.data)?)
+}
+
+/// Fetches the token URI on-chain and resolves it into `TokenMetadata`.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `fetcher` - The fetcher used for off-chain URIs.
+/// * `contract` - The address of the collection contract.
+/// * `token_id` - The id of the token.
+/// * `block` - The block at which the contract is called.
+pub async fn fetch_token_metadata<C, F>(
+    client: &C,
+    fetcher: &F,
+    contract: FieldElement,
+    token_id: CairoU256,
+    block: BlockId,
+) -> Result<TokenMetadata, MetadataFetchError>
+where
+    C: StarknetClient + Sync + ?Sized,
+    F: MetadataFetcher + Sync + ?Sized,
+{
+    let token_uri = fetch_token_uri(client, contract, token_id, block).await?;
+    resolve_token_metadata(fetcher, &token_uri.uri).await
+}
+
+/// Returns the mime type of a `Content-Type` value, without parameters and in lowercase.
+pub(crate) fn mime_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local::InMemoryMetadataFetcher;
+    use super::*;
+    use crate::starknet_utils::client::{MockStarknetClient, StarknetClientError};
+    use starknet::core::types::BlockTag;
+    use starknet::core::utils::cairo_short_string_to_felt;
+    use starknet::macros::{felt, selector};
+
+    const JSON: &str = r#"{"name":"Token #1"}"#;
+
+    #[tokio::test]
+    async fn on_chain_uris_are_not_fetched() {
+        let mut fetcher = MockMetadataFetcher::new();
+        fetcher.expect_fetch().never();
+
+        for uri in [
+            format!(" {} ", JSON),
+            format!("data:application/json;utf8,{}", JSON),
+            "data:application/json;base64,eyJuYW1lIjoiVG9rZW4gIzEifQ==".to_string(),
+        ] {
+            let metadata = resolve_token_metadata(&fetcher, &uri).await.unwrap();
+
+            assert_eq!(metadata.name.as_deref(), Some("Token #1"), "{}", uri);
+        }
+
+        assert!(matches!(
+            resolve_token_metadata(&fetcher, "data:image/png;base64,AAAA").await,
+            Err(MetadataFetchError::Metadata(
+                MetadataError::UnsupportedMimeType(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn off_chain_uris_are_fetched() {
+        let mut fetcher = InMemoryMetadataFetcher::new();
+        fetcher.insert_json("ipfs://bafy/1.json", JSON);
+        fetcher.insert_json("ipfs://bafy/2.json", "<html></html>");
+
+        let metadata = resolve_token_metadata(&fetcher, " ipfs://bafy/1.json\n")
+            .await
+            .unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("Token #1"));
+
+        assert!(matches!(
+            resolve_token_metadata(&fetcher, "ipfs://bafy/2.json").await,
+            Err(MetadataFetchError::Metadata(MetadataError::Json(_)))
+        ));
+        assert!(matches!(
+            resolve_token_metadata(&fetcher, "ipfs://bafy/3.json").await,
+            Err(MetadataFetchError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn token_metadata_from_the_contract() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, selector, _, _| {
+                if selector == selector!("token_uri") {
+                    Ok(vec![
+                        cairo_short_string_to_felt("ipfs://bafy/1.json").unwrap()
+                    ])
+                } else {
+                    Err(StarknetClientError::EntrypointNotFound("".to_string()))
+                }
+            });
+        let mut fetcher = InMemoryMetadataFetcher::new();
+        fetcher.insert_json("ipfs://bafy/1.json", JSON);
+
+        let metadata = fetch_token_metadata(
+            &client,
+            &fetcher,
+            felt!("0xc011"),
+            CairoU256::from(1_u64),
+            BlockId::Tag(BlockTag::Latest),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(metadata.name.as_deref(), Some("Token #1"));
+    }
+
+    #[test]
+    fn mime_type_of_content_type() {
+        assert_eq!(
+            mime_type("Application/JSON; charset=utf-8"),
+            "application/json"
+        );
+        assert_eq!(mime_type(" text/plain "), "text/plain");
+        assert_eq!(mime_type(""), "");
+    }
+}
@@ -0,0 +1,508 @@
+//! In-process ledger of token ownership, materialized from decoded transfers.
+//!
+//! The ledger keeps the current owner of every ERC-721 token and the balances
+//! of every ERC-1155 token. Each change is journaled with its block so that
+//! the blocks replaced by a reorg can be rolled back.
+use serde::{Deserialize, Serialize};
+use starknet::core::types::FieldElement;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Bound;
+use std::path::Path;
+
+use super::events::{TokenStandard, TokenTransfer};
+use crate::starknet_utils::CairoU256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LedgerError {
+    #[error("Transfer of block {block} applied after block {last}")]
+    OutOfOrder { block: u64, last: u64 },
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid snapshot: {0}")]
+    Snapshot(#[from] serde_json::Error),
+}
+
+/// Identifies a token of a collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TokenKey {
+    pub contract: FieldElement,
+    pub token_id: CairoU256,
+}
+
+/// Identifies the balance of an account for an ERC-1155 token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BalanceKey {
+    pub contract: FieldElement,
+    pub token_id: CairoU256,
+    pub owner: FieldElement,
+}
+
+/// A change applied to the ledger, with the value it replaced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum LedgerChange {
+    Owner {
+        key: TokenKey,
+        previous: Option<FieldElement>,
+    },
+    Balance {
+        key: BalanceKey,
+        previous: Option<CairoU256>,
+    },
+}
+
+/// On-disk representation of the ledger. Maps are stored as lists
+/// as their keys are not strings.
+#[derive(Serialize, Deserialize)]
+struct LedgerSnapshot {
+    owners: Vec<(TokenKey, FieldElement)>,
+    balances: Vec<(BalanceKey, CairoU256)>,
+    journal: Vec<(u64, Vec<LedgerChange>)>,
+    pending: Vec<LedgerChange>,
+    last_block: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OwnershipLedger {
+    owners: HashMap<TokenKey, FieldElement>,
+    balances: HashMap<BalanceKey, CairoU256>,
+    /// Changes applied by each block, in the order they were applied.
+    journal: BTreeMap<u64, Vec<LedgerChange>>,
+    /// Changes applied by transfers of the pending block.
+    pending: Vec<LedgerChange>,
+    last_block: Option<u64>,
+}
+
+impl OwnershipLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the last block applied to the ledger, pending transfers excluded.
+    pub fn last_block(&self) -> Option<u64> {
+        self.last_block
+    }
+
+    /// Returns the current owner of an ERC-721 token,
+    /// `None` if the token was burnt or never seen.
+    pub fn owner_of(&self, contract: FieldElement, token_id: CairoU256) -> Option<FieldElement> {
+        self.owners.get(&TokenKey { contract, token_id }).copied()
+    }
+
+    /// Returns the balance of `owner` for an ERC-1155 token.
+    pub fn balance_of(
+        &self,
+        contract: FieldElement,
+        token_id: CairoU256,
+        owner: FieldElement,
+    ) -> CairoU256 {
+        self.balances
+            .get(&BalanceKey {
+                contract,
+                token_id,
+                owner,
+            })
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the owners of all the ERC-721 tokens.
+    pub fn owners(&self) -> impl Iterator<Item = (&TokenKey, &FieldElement)> {
+        self.owners.iter()
+    }
+
+    /// Returns all the non-zero ERC-1155 balances.
+    pub fn balances(&self) -> impl Iterator<Item = (&BalanceKey, &CairoU256)> {
+        self.balances.iter()
+    }
+
+    /// Applies the transfers in order, see `apply_transfer`.
+    pub fn apply_transfers<'a, I>(&mut self, transfers: I) -> Result<(), LedgerError>
+    where
+        I: IntoIterator<Item = &'a TokenTransfer>,
+    {
+        transfers
+            .into_iter()
+            .try_for_each(|transfer| self.apply_transfer(transfer))
+    }
+
+    /// Applies a transfer to the ledger. Transfers without block number are
+    /// considered pending and can be reverted with `revert_pending`. The pending
+    /// transfers are reverted before applying a transfer of a block, which
+    /// contains them once accepted.
+    ///
+    /// The zero address as sender is a mint, as receiver a burn.
+    /// As the ledger may not start at the deployment of the collection,
+    /// ERC-1155 balances saturate at zero instead of failing.
+    ///
+    /// # Arguments
+    ///
+    /// * `transfer` - The transfer, which must not be older than the last applied block.
+    pub fn apply_transfer(&mut self, transfer: &TokenTransfer) -> Result<(), LedgerError> {
+        if let (Some(block), Some(last)) = (transfer.block_number, self.last_block) {
+            if block < last {
+                return Err(LedgerError::OutOfOrder { block, last });
+            }
+        }
+
+        if transfer.block_number.is_some() {
+            self.revert_pending();
+        }
+
+        let changes = match transfer.standard {
+            TokenStandard::ERC721 => vec![self.set_owner(transfer)],
+            TokenStandard::ERC1155 => self.move_balance(transfer),
+        };
+
+        match transfer.block_number {
+            Some(block) => {
+                self.journal.entry(block).or_default().extend(changes);
+                self.last_block = Some(block);
+            }
+            None => self.pending.extend(changes),
+        };
+
+        Ok(())
+    }
+
+    /// Reverts the transfers of the pending block, to apply them again
+    /// once the block is accepted or replaced.
+    pub fn revert_pending(&mut self) {
+        let changes = std::mem::take(&mut self.pending);
+        self.undo(changes);
+    }
+
+    /// Reverts the transfers of all blocks after `block_number`, and the pending ones.
+    /// On a reorg, this must be called with the last block that was not replaced.
+    pub fn rollback_to(&mut self, block_number: u64) {
+        self.revert_pending();
+
+        let reverted: Vec<u64> = self
+            .journal
+            .range((Bound::Excluded(block_number), Bound::Unbounded))
+            .map(|(block, _)| *block)
+            .collect();
+        for block in reverted.into_iter().rev() {
+            if let Some(changes) = self.journal.remove(&block) {
+                self.undo(changes);
+            }
+        }
+
+        // The journal may have been pruned up to a later block.
+        self.last_block = self.last_block.map(|last| last.min(block_number));
+    }
+
+    /// Drops the journal of blocks up to `block_number` included, which can't be
+    /// rolled back afterwards. Must be called with finalized blocks to bound memory.
+    pub fn prune_journal(&mut self, block_number: u64) {
+        self.journal.retain(|block, _| *block > block_number);
+    }
+
+    /// Writes the ledger, journal included, to a JSON file.
+    /// The file is replaced atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LedgerError> {
+        let path = path.as_ref();
+
+        let snapshot = LedgerSnapshot {
+            owners: self.owners.iter().map(|(k, v)| (*k, *v)).collect(),
+            balances: self.balances.iter().map(|(k, v)| (*k, *v)).collect(),
+            journal: self.journal.iter().map(|(b, c)| (*b, c.clone())).collect(),
+            pending: self.pending.clone(),
+            last_block: self.last_block,
+        };
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&snapshot)?)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Restores a ledger written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LedgerError> {
+        let snapshot: LedgerSnapshot = serde_json::from_slice(&fs::read(path)?)?;
+
+        Ok(Self {
+            owners: snapshot.owners.into_iter().collect(),
+            balances: snapshot.balances.into_iter().collect(),
+            journal: snapshot.journal.into_iter().collect(),
+            pending: snapshot.pending,
+            last_block: snapshot.last_block,
+        })
+    }
+
+    fn set_owner(&mut self, transfer: &TokenTransfer) -> LedgerChange {
+        let key = TokenKey {
+            contract: transfer.contract_address,
+            token_id: transfer.token_id,
+        };
+
+        let previous = if transfer.to == FieldElement::ZERO {
+            self.owners.remove(&key)
+        } else {
+            self.owners.insert(key, transfer.to)
+        };
+
+        LedgerChange::Owner { key, previous }
+    }
+
+    fn move_balance(&mut self, transfer: &TokenTransfer) -> Vec<LedgerChange> {
+        let key = |owner| BalanceKey {
+            contract: transfer.contract_address,
+            token_id: transfer.token_id,
+            owner,
+        };
+
+        let mut changes = vec![];
+
+        if transfer.from != FieldElement::ZERO {
+            let key = key(transfer.from);
+            let balance = self.balances.get(&key).copied().unwrap_or_default();
+            changes.push(self.set_balance(key, balance.saturating_sub(transfer.amount)));
+        }
+
+        if transfer.to != FieldElement::ZERO {
+            let key = key(transfer.to);
+            let balance = self.balances.get(&key).copied().unwrap_or_default();
+            changes.push(self.set_balance(key, balance.saturating_add(transfer.amount)));
+        }
+
+        changes
+    }
+
+    /// Sets a balance, zero balances are removed.
+    fn set_balance(&mut self, key: BalanceKey, balance: CairoU256) -> LedgerChange {
+        let previous = if balance.is_zero() {
+            self.balances.remove(&key)
+        } else {
+            self.balances.insert(key, balance)
+        };
+
+        LedgerChange::Balance { key, previous }
+    }
+
+    /// Restores the previous values, undoing the changes from the last one.
+    fn undo(&mut self, changes: Vec<LedgerChange>) {
+        for change in changes.into_iter().rev() {
+            match change {
+                LedgerChange::Owner { key, previous } => {
+                    match previous {
+                        Some(owner) => self.owners.insert(key, owner),
+                        None => self.owners.remove(&key),
+                    };
+                }
+                LedgerChange::Balance { key, previous } => {
+                    match previous {
+                        Some(balance) => self.balances.insert(key, balance),
+                        None => self.balances.remove(&key),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::macros::felt;
+
+    const COLLECTION: FieldElement = felt!("0xc011");
+    const ALICE: FieldElement = felt!("0xa11ce");
+    const BOB: FieldElement = felt!("0xb0b");
+
+    fn transfer(from: FieldElement, to: FieldElement, block_number: Option<u64>) -> TokenTransfer {
+        TokenTransfer {
+            contract_address: COLLECTION,
+            transaction_hash: felt!("0x1"),
+            block_number,
+            standard: TokenStandard::ERC721,
+            operator: None,
+            from,
+            to,
+            token_id: CairoU256::ONE,
+            amount: CairoU256::ONE,
+        }
+    }
+
+    fn owner(ledger: &OwnershipLedger) -> Option<FieldElement> {
+        ledger.owner_of(COLLECTION, CairoU256::ONE)
+    }
+
+    #[test]
+    fn pending_transfers_are_replaced_by_their_block() {
+        let mut ledger = OwnershipLedger::new();
+        ledger
+            .apply_transfer(&transfer(FieldElement::ZERO, ALICE, None))
+            .unwrap();
+
+        // The pending block is accepted with one more transfer.
+        ledger
+            .apply_transfers(&[
+                transfer(FieldElement::ZERO, ALICE, Some(1)),
+                transfer(ALICE, BOB, Some(1)),
+            ])
+            .unwrap();
+        ledger.revert_pending();
+        assert_eq!(owner(&ledger), Some(BOB));
+
+        ledger.rollback_to(0);
+        assert_eq!(owner(&ledger), None);
+        assert_eq!(ledger.last_block(), Some(0));
+    }
+
+    #[test]
+    fn rollback_and_prune_at_the_last_block_number() {
+        let mut ledger = OwnershipLedger::new();
+        ledger
+            .apply_transfer(&transfer(FieldElement::ZERO, ALICE, Some(u64::MAX)))
+            .unwrap();
+
+        ledger.rollback_to(u64::MAX);
+        assert_eq!(owner(&ledger), Some(ALICE));
+        assert_eq!(ledger.last_block(), Some(u64::MAX));
+
+        ledger.prune_journal(u64::MAX);
+        ledger.rollback_to(0);
+        assert_eq!(owner(&ledger), Some(ALICE));
+    }
+
+    #[test]
+    fn rollback_after_prune_keeps_the_order_guard() {
+        let mut ledger = OwnershipLedger::new();
+        ledger
+            .apply_transfers(&[
+                transfer(FieldElement::ZERO, ALICE, Some(5)),
+                transfer(ALICE, BOB, Some(10)),
+            ])
+            .unwrap();
+        ledger.prune_journal(10);
+
+        // Nothing left to roll back, the last block is unchanged.
+        ledger.rollback_to(20);
+        assert_eq!(ledger.last_block(), Some(10));
+        assert!(matches!(
+            ledger.apply_transfer(&transfer(BOB, ALICE, Some(9))),
+            Err(LedgerError::OutOfOrder { block: 9, last: 10 })
+        ));
+
+        // The journal of the replaced blocks is gone, their changes are kept.
+        ledger.rollback_to(7);
+        assert_eq!(ledger.last_block(), Some(7));
+        assert_eq!(owner(&ledger), Some(BOB));
+        assert!(ledger
+            .apply_transfer(&transfer(BOB, ALICE, Some(6)))
+            .is_err());
+        assert!(ledger
+            .apply_transfer(&transfer(BOB, ALICE, Some(8)))
+            .is_ok());
+    }
+
+    fn erc1155(
+        from: FieldElement,
+        to: FieldElement,
+        amount: u64,
+        block_number: Option<u64>,
+    ) -> TokenTransfer {
+        TokenTransfer {
+            standard: TokenStandard::ERC1155,
+            operator: Some(from),
+            amount: CairoU256::from(amount),
+            ..transfer(from, to, block_number)
+        }
+    }
+
+    fn balance(ledger: &OwnershipLedger, owner: FieldElement) -> u64 {
+        let balance = ledger.balance_of(COLLECTION, CairoU256::ONE, owner);
+        u64::try_from(balance.low).unwrap()
+    }
+
+    #[test]
+    fn erc1155_balances() {
+        let mut ledger = OwnershipLedger::new();
+        ledger
+            .apply_transfers(&[
+                erc1155(FieldElement::ZERO, ALICE, 10, Some(1)),
+                erc1155(ALICE, BOB, 4, Some(2)),
+            ])
+            .unwrap();
+        assert_eq!(balance(&ledger, ALICE), 6);
+        assert_eq!(balance(&ledger, BOB), 4);
+        // ERC-1155 transfers don't change the owners.
+        assert_eq!(owner(&ledger), None);
+
+        // Burn, and a transfer of more than the known balance.
+        ledger
+            .apply_transfers(&[
+                erc1155(BOB, FieldElement::ZERO, 4, Some(3)),
+                erc1155(ALICE, BOB, 8, Some(3)),
+            ])
+            .unwrap();
+        assert_eq!(balance(&ledger, ALICE), 0);
+        assert_eq!(balance(&ledger, BOB), 8);
+        // Zero balances are removed.
+        assert_eq!(ledger.balances().count(), 1);
+
+        ledger.rollback_to(1);
+        assert_eq!(balance(&ledger, ALICE), 10);
+        assert_eq!(balance(&ledger, BOB), 0);
+
+        ledger
+            .apply_transfer(&erc1155(ALICE, BOB, 1, None))
+            .unwrap();
+        assert_eq!(balance(&ledger, BOB), 1);
+        ledger.revert_pending();
+        assert_eq!(balance(&ledger, ALICE), 10);
+        assert_eq!(balance(&ledger, BOB), 0);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let path = std::env::temp_dir().join(format!("ledger-{}.json", std::process::id()));
+
+        let mut ledger = OwnershipLedger::new();
+        ledger
+            .apply_transfers(&[
+                transfer(FieldElement::ZERO, ALICE, Some(1)),
+                erc1155(FieldElement::ZERO, ALICE, 10, Some(1)),
+                transfer(ALICE, BOB, Some(2)),
+                erc1155(ALICE, BOB, 3, Some(2)),
+                erc1155(BOB, ALICE, 1, None),
+            ])
+            .unwrap();
+        ledger.save(&path).unwrap();
+
+        let mut loaded = OwnershipLedger::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.last_block(), Some(2));
+        assert_eq!(owner(&loaded), Some(BOB));
+        assert_eq!(balance(&loaded, ALICE), 8);
+        assert_eq!(balance(&loaded, BOB), 2);
+
+        // The pending transfers and the journal are restored.
+        loaded.revert_pending();
+        assert_eq!(balance(&loaded, BOB), 3);
+        loaded.rollback_to(1);
+        assert_eq!(owner(&loaded), Some(ALICE));
+        assert_eq!(balance(&loaded, ALICE), 10);
+        assert_eq!(balance(&loaded, BOB), 0);
+    }
+
+    #[test]
+    fn invalid_snapshot() {
+        let path = std::env::temp_dir().join(format!("ledger-invalid-{}.json", std::process::id()));
+        std::fs::write(&path, "{}").unwrap();
+
+        assert!(matches!(
+            OwnershipLedger::load(&path),
+            Err(LedgerError::Snapshot(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            OwnershipLedger::load(&path),
+            Err(LedgerError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,372 @@
+//! Decoding of ERC-721 metadata JSON, for token URIs that embed the
+//! metadata on-chain as `data:` URIs.
+//!
+//! Supported forms of token URI:
+//! * `data:application/json;base64,<base64>`
+//! * `data:application/json;utf8,<json>` or `data:application/json,<percent-encoded json>`,
+//!   the JSON being percent-decoded only if it is percent-encoded.
+//! * A raw JSON object.
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+const DATA_URI_PREFIX: &str = "data:";
+const SVG_MIME_TYPE: &str = "image/svg+xml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    #[error("Not a data URI")]
+    NotDataUri,
+    #[error("Invalid data URI: {0}")]
+    InvalidDataUri(String),
+    #[error("Unsupported mime type: {0}")]
+    UnsupportedMimeType(String),
+    #[error("Invalid base64 content: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Invalid UTF-8 content")]
+    Utf8,
+    #[error("Invalid metadata JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A decoded `data:` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUri {
+    /// Mime type in lowercase, `text/plain` if not specified.
+    pub mime_type: String,
+    /// Parameters other than `base64`, like `charset=utf-8` or `utf8`.
+    pub parameters: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+/// ERC-721 metadata JSON.
+/// Fields that are not part of the standard are kept in `extra`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Raw SVG image, as an alternative to `image`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<MetadataAttribute>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataAttribute {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trait_type: Option<String>,
+    #[serde(default)]
+    pub value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_type: Option<String>,
+}
+
+/// An image embedded in the metadata, as bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedImage {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl TokenMetadata {
+    /// Parses metadata from JSON bytes.
+    pub fn from_json(json: &[u8]) -> Result<Self, MetadataError> {
+        Ok(serde_json::from_slice(json)?)
+    }
+
+    /// Returns the image embedded in the metadata, from `image` if it is
+    /// a `data:` URI, or from `image_data` which contains a raw SVG.
+    /// Returns `None` if the image is not embedded (`ipfs://`, `https://`...).
+    pub fn embedded_image(&self) -> Option<Result<EmbeddedImage, MetadataError>> {
+        if let Some(image) = self.image.as_deref() {
+            if image.starts_with(DATA_URI_PREFIX) {
+                return Some(parse_data_uri(image).map(|uri| EmbeddedImage {
+                    mime_type: uri.mime_type,
+                    data: uri.data,
+                }));
+            }
+        }
+
+        if let Some(image_data) = self.image_data.as_deref() {
+            if image_data.trim_start().starts_with('<') {
+                return Some(Ok(EmbeddedImage {
+                    mime_type: SVG_MIME_TYPE.to_string(),
+                    data: image_data.as_bytes().to_vec(),
+                }));
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns true if the URI embeds its content.
+pub fn is_data_uri(uri: &str) -> bool {
+    uri.trim_start().starts_with(DATA_URI_PREFIX)
+}
+
+/// Decodes the metadata embedded in a token URI, as returned by
+/// `parse_cairo_string` or `fetch_token_uri`.
+/// Returns `MetadataError::NotDataUri` if the metadata must be fetched off-chain.
+///
+/// # Arguments
+///
+/// * `uri` - The token URI.
+pub fn decode_token_metadata(uri: &str) -> Result<TokenMetadata, MetadataError> {
+    let uri = uri.trim();
+
+    // Some contracts return the JSON directly.
+    if uri.starts_with('{') {
+        return TokenMetadata::from_json(uri.as_bytes());
+    }
+
+    let data_uri = parse_data_uri(uri)?;
+
+    match data_uri.mime_type.as_str() {
+        "application/json" | "text/json" | "text/plain" => TokenMetadata::from_json(&data_uri.data),
+        other => Err(MetadataError::UnsupportedMimeType(other.to_string())),
+    }
+}
+
+/// Parses a `data:[<mime type>][;<parameter>...][;base64],<data>` URI.
+pub fn parse_data_uri(uri: &str) -> Result<DataUri, MetadataError> {
+    let content = uri
+        .trim()
+        .strip_prefix(DATA_URI_PREFIX)
+        .ok_or(MetadataError::NotDataUri)?;
+
+    let (header, data) = content
+        .split_once(',')
+        .ok_or_else(|| MetadataError::InvalidDataUri("missing ',' separator".to_string()))?;
+
+    let mut parts = header.split(';').map(str::trim);
+
+    let mime_type = match parts.next() {
+        Some(m) if !m.is_empty() => m.to_lowercase(),
+        _ => "text/plain".to_string(),
+    };
+
+    let mut is_base64 = false;
+    let mut parameters = vec![];
+    for p in parts {
+        if p.eq_ignore_ascii_case("base64") {
+            is_base64 = true;
+        } else if !p.is_empty() {
+            parameters.push(p.to_string());
+        }
+    }
+
+    let data = if is_base64 {
+        decode_base64(data)?
+    } else if is_percent_encoded(data) {
+        percent_decode_str(data).collect()
+    } else {
+        data.as_bytes().to_vec()
+    };
+
+    Ok(DataUri {
+        mime_type,
+        parameters,
+        data,
+    })
+}
+
+/// Returns true if the data is percent-encoded. Raw content, like the JSON
+/// of most `utf8` data URIs, contains characters that can't appear in a URI:
+/// decoding its `%` sequences would corrupt it.
+fn is_percent_encoded(data: &str) -> bool {
+    !data
+        .chars()
+        .any(|c| !c.is_ascii() || c.is_ascii_whitespace() || "\"{}<>\\^`|".contains(c))
+}
+
+/// Decodes base64 content, accepting the standard and URL safe alphabets,
+/// with or without padding.
+fn decode_base64(data: &str) -> Result<Vec<u8>, MetadataError> {
+    // Base64 content may be percent-encoded or contain line breaks.
+    let data: String = percent_decode_str(data)
+        .decode_utf8()
+        .map_err(|_| MetadataError::Utf8)?
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    let mut last_error = None;
+    for engine in [&STANDARD, &STANDARD_NO_PAD, &URL_SAFE, &URL_SAFE_NO_PAD] {
+        match engine.decode(&data) {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    // Safe to unwrap as at least one engine was tried.
+    Err(last_error.unwrap().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{"name":"Token #1","description":"100%25 on-chain","attributes":[{"trait_type":"Eyes","value":"Blue"}],"rarity":3}"#;
+
+    #[test]
+    fn base64_data_uri() {
+        let standard = STANDARD.encode(JSON);
+        let url_safe = URL_SAFE_NO_PAD.encode(JSON);
+        let wrapped = format!("{}\n{}", &standard[..20], &standard[20..]);
+        let percent_encoded = standard.replace('=', "%3D").replace('+', "%2B");
+
+        for data in [standard.clone(), url_safe, wrapped, percent_encoded] {
+            let uri = format!("data:application/json;base64,{}", data);
+
+            let data_uri = parse_data_uri(&uri).unwrap();
+
+            assert_eq!(data_uri.mime_type, "application/json");
+            assert!(data_uri.parameters.is_empty());
+            assert_eq!(data_uri.data, JSON.as_bytes());
+        }
+
+        let metadata =
+            decode_token_metadata(&format!("data:application/json;base64,{}", standard)).unwrap();
+        assert_eq!(metadata.name.as_deref(), Some("Token #1"));
+        assert_eq!(metadata.description.as_deref(), Some("100%25 on-chain"));
+        assert_eq!(metadata.attributes.len(), 1);
+        assert_eq!(metadata.extra["rarity"], 3);
+    }
+
+    #[test]
+    fn utf8_data_uri_is_not_percent_decoded() {
+        let uri = format!("data:application/json;utf8,{}", JSON);
+
+        let data_uri = parse_data_uri(&uri).unwrap();
+
+        assert_eq!(data_uri.parameters, vec!["utf8"]);
+        // `%25` is kept as is.
+        assert_eq!(data_uri.data, JSON.as_bytes());
+
+        let metadata = decode_token_metadata(&uri).unwrap();
+        assert_eq!(metadata.description.as_deref(), Some("100%25 on-chain"));
+
+        // Raw JSON with non-ASCII characters and spaces.
+        let uri = r#"data:application/json;charset=utf-8,{"name": "Jeton été 50%"}"#;
+        assert_eq!(
+            decode_token_metadata(uri).unwrap().name.as_deref(),
+            Some("Jeton été 50%")
+        );
+    }
+
+    #[test]
+    fn percent_encoded_data_uri() {
+        let uri = "data:application/json,%7B%22name%22%3A%22Token%20%231%22%7D";
+
+        let data_uri = parse_data_uri(uri).unwrap();
+
+        assert_eq!(data_uri.data, br#"{"name":"Token #1"}"#);
+        assert_eq!(
+            decode_token_metadata(uri).unwrap().name.as_deref(),
+            Some("Token #1")
+        );
+    }
+
+    #[test]
+    fn data_uri_header() {
+        let data_uri = parse_data_uri("data:,Hello%2C%20World").unwrap();
+        assert_eq!(data_uri.mime_type, "text/plain");
+        assert_eq!(data_uri.data, b"Hello, World");
+
+        let data_uri =
+            parse_data_uri(" data:Image/SVG+XML;charset=UTF-8;BASE64,PHN2Zy8+ ").unwrap();
+        assert_eq!(data_uri.mime_type, "image/svg+xml");
+        assert_eq!(data_uri.parameters, vec!["charset=UTF-8"]);
+        assert_eq!(data_uri.data, b"<svg/>");
+    }
+
+    #[test]
+    fn malformed_uris() {
+        assert!(matches!(
+            parse_data_uri("ipfs://bafy/1.json"),
+            Err(MetadataError::NotDataUri)
+        ));
+        assert!(matches!(
+            decode_token_metadata("https://example.com/1.json"),
+            Err(MetadataError::NotDataUri)
+        ));
+        assert!(matches!(
+            parse_data_uri("data:application/json;base64"),
+            Err(MetadataError::InvalidDataUri(_))
+        ));
+        assert!(matches!(
+            parse_data_uri("data:application/json;base64,not base64!"),
+            Err(MetadataError::Base64(_))
+        ));
+        assert!(matches!(
+            parse_data_uri("data:application/json;base64,%FF"),
+            Err(MetadataError::Utf8)
+        ));
+        assert!(matches!(
+            decode_token_metadata("data:image/png;base64,AAAA"),
+            Err(MetadataError::UnsupportedMimeType(m)) if m == "image/png"
+        ));
+        assert!(matches!(
+            decode_token_metadata(r#"data:application/json;utf8,{"name":"#),
+            Err(MetadataError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn raw_json_token_uri() {
+        let metadata = decode_token_metadata(&format!("  {}  ", JSON)).unwrap();
+
+        assert_eq!(metadata.name.as_deref(), Some("Token #1"));
+        assert_eq!(
+            serde_json::to_value(&metadata).unwrap(),
+            serde_json::from_str::<Value>(JSON).unwrap()
+        );
+    }
+
+    #[test]
+    fn embedded_images() {
+        let metadata = TokenMetadata {
+            image: Some("data:image/svg+xml;base64,PHN2Zy8+".to_string()),
+            ..Default::default()
+        };
+        let image = metadata.embedded_image().unwrap().unwrap();
+        assert_eq!(image.mime_type, SVG_MIME_TYPE);
+        assert_eq!(image.data, b"<svg/>");
+
+        let metadata = TokenMetadata {
+            image: Some("ipfs://bafy/1.png".to_string()),
+            image_data: Some("  <svg/>".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata.embedded_image().unwrap().unwrap().data,
+            b"  <svg/>"
+        );
+
+        let metadata = TokenMetadata {
+            image: Some("ipfs://bafy/1.png".to_string()),
+            ..Default::default()
+        };
+        assert!(metadata.embedded_image().is_none());
+
+        let metadata = TokenMetadata {
+            image: Some("data:image/png;base64,!".to_string()),
+            ..Default::default()
+        };
+        assert!(metadata.embedded_image().unwrap().is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! Token ownership and balance queries, for ERC-721 and ERC-1155.
+use starknet::core::types::{BlockId, FieldElement};
+use starknet::core::utils::cairo_short_string_to_felt;
+
+use super::contract_type::{BALANCE_OF_SELECTORS, OWNER_OF_SELECTORS};
+use super::token_uri::call_with_token_id;
+use crate::starknet_utils::client::{call_contract_selectors, StarknetClient, StarknetClientError};
+use crate::starknet_utils::CairoU256;
+
+/// Revert reasons of the known implementations when the token does not exist.
+/// They are matched in plain text and as hexadecimal short strings, as Cairo 1
+/// contracts only report the latter.
+const NONEXISTENT_TOKEN_REASONS: [&str; 5] = [
+    "ERC721: invalid token ID",
+    "ERC721: owner query for nonexistent token",
+    "ERC721: token_id is not a valid Uint256",
+    "ERC721: nonexistent token",
+    "ERC1155: invalid token ID",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum OwnershipError {
+    /// The token was burnt or never minted.
+    #[error("Token {token_id} does not exist in contract {contract:#x}")]
+    TokenNotFound {
+        contract: FieldElement,
+        token_id: CairoU256,
+    },
+    #[error("Call failed: {0}")]
+    Client(#[from] StarknetClientError),
+    #[error("Invalid value returned: {0}")]
+    InvalidValue(String),
+}
+
+/// Returns the owner of an ERC-721 token.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `contract` - The address of the collection contract.
+/// * `token_id` - The id of the token.
+/// * `block` - The block at which the contract is called.
+pub async fn owner_of<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract: FieldElement,
+    token_id: CairoU256,
+    block: BlockId,
+) -> Result<FieldElement, OwnershipError> {
+    let not_found = || OwnershipError::TokenNotFound { contract, token_id };
+
+    let felts = match call_with_token_id(
+        client,
+        contract,
+        &OWNER_OF_SELECTORS,
+        token_id,
+        block,
+        |id| id.to_vec(),
+    )
+    .await
+    {
+        Ok((felts, _)) => felts,
+        Err(StarknetClientError::Contract(reason)) if is_nonexistent_token(&reason) => {
+            return Err(not_found())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    match felts[..] {
+        // Some implementations return the zero address instead of reverting.
+        [owner] if owner == FieldElement::ZERO => Err(not_found()),
+        [owner] => Ok(owner),
+        _ => Err(OwnershipError::InvalidValue(format!(
+            "unexpected owner length {}",
+            felts.len()
+        ))),
+    }
+}
+
+/// Returns the balance of `owner`. For ERC-1155, `token_id` must be given,
+/// for ERC-721 it must be `None` and the number of tokens owned is returned.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `contract` - The address of the collection contract.
+/// * `owner` - The account to get the balance of.
+/// * `token_id` - The id of the token for ERC-1155.
+/// * `block` - The block at which the contract is called.
+pub async fn balance_of<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract: FieldElement,
+    owner: FieldElement,
+    token_id: Option<CairoU256>,
+    block: BlockId,
+) -> Result<CairoU256, OwnershipError> {
+    let result = match token_id {
+        None => {
+            call_contract_selectors(client, contract, &BALANCE_OF_SELECTORS, vec![owner], block)
+                .await
+        }
+        Some(token_id) => call_with_token_id(
+            client,
+            contract,
+            &BALANCE_OF_SELECTORS,
+            token_id,
+            block,
+            |id| [&[owner], id].concat(),
+        )
+        .await
+        .map(|(felts, _)| felts),
+    };
+
+    let felts = match (result, token_id) {
+        (Ok(felts), _) => felts,
+        (Err(StarknetClientError::Contract(reason)), Some(token_id))
+            if is_nonexistent_token(&reason) =>
+        {
+            return Err(OwnershipError::TokenNotFound { contract, token_id })
+        }
+        (Err(e), _) => return Err(e.into()),
+    };
+
+    match felts[..] {
+        [value] => Ok(CairoU256::from(value)),
+        [low, high] => CairoU256::from_felts(low, high)
+            .map_err(|e| OwnershipError::InvalidValue(e.to_string())),
+        _ => Err(OwnershipError::InvalidValue(format!(
+            "unexpected balance length {}",
+            felts.len()
+        ))),
+    }
+}
+
+/// Returns true if the revert reason means that the token does not exist.
+fn is_nonexistent_token(reason: &str) -> bool {
+    NONEXISTENT_TOKEN_REASONS.iter().any(|r| {
+        reason.contains(r)
+            || cairo_short_string_to_felt(r)
+                .is_ok_and(|felt| reason.contains(&format!("{:#x}", felt)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::macros::{felt, selector};
+
+    const CONTRACT: FieldElement = felt!("0xc011");
+    const OWNER: FieldElement = felt!("0xa11ce");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    fn token_id() -> CairoU256 {
+        CairoU256::from(7_u64)
+    }
+
+    /// A contract answering `entrypoint` with `result`, an error being a revert
+    /// reason, up to the given number of calldata felts, and rejecting longer
+    /// calldata like Cairo 0 does.
+    fn mock(
+        entrypoint: FieldElement,
+        calldata_len: usize,
+        result: Result<Vec<FieldElement>, String>,
+    ) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(move |contract, selector, calldata, _| {
+                assert_eq!(contract, CONTRACT);
+                match (selector == entrypoint, calldata.len()) {
+                    (false, _) => Err(StarknetClientError::EntrypointNotFound("".to_string())),
+                    (true, len) if len > calldata_len => Err(StarknetClientError::InputTooLong),
+                    (true, _) => result.clone().map_err(StarknetClientError::Contract),
+                }
+            });
+        client
+    }
+
+    fn reverted(reason: &str) -> Result<Vec<FieldElement>, String> {
+        Err(format!("Error in the called contract: {}", reason))
+    }
+
+    #[tokio::test]
+    async fn owner_of_cairo1_and_camel_case() {
+        for entrypoint in [selector!("owner_of"), selector!("ownerOf")] {
+            let client = mock(entrypoint, 2, Ok(vec![OWNER]));
+
+            let owner = owner_of(&client, CONTRACT, token_id(), BLOCK)
+                .await
+                .unwrap();
+
+            assert_eq!(owner, OWNER);
+        }
+    }
+
+    #[tokio::test]
+    async fn owner_of_cairo0_felt_token_id() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, selector, calldata, _| {
+                if selector != selector!("owner_of") {
+                    return Err(StarknetClientError::EntrypointNotFound("".to_string()));
+                }
+                match calldata[..] {
+                    [id] if id == felt!("0x7") => Ok(vec![OWNER]),
+                    _ => Err(StarknetClientError::InputTooLong),
+                }
+            });
+
+        let owner = owner_of(&client, CONTRACT, token_id(), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(owner, OWNER);
+    }
+
+    #[tokio::test]
+    async fn owner_of_nonexistent_token() {
+        let short_string = cairo_short_string_to_felt("ERC721: invalid token ID").unwrap();
+
+        for result in [
+            reverted("ERC721: owner query for nonexistent token"),
+            // Cairo 1 contracts report the reason as a short string.
+            reverted(&format!("Failure reason: {:#x}.", short_string)),
+            // Some implementations return the zero address.
+            Ok(vec![FieldElement::ZERO]),
+        ] {
+            let client = mock(selector!("owner_of"), 2, result);
+
+            let error = owner_of(&client, CONTRACT, token_id(), BLOCK)
+                .await
+                .unwrap_err();
+
+            assert!(matches!(
+                error,
+                OwnershipError::TokenNotFound { contract, token_id: id }
+                    if contract == CONTRACT && id == token_id()
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn owner_of_other_errors() {
+        let client = mock(
+            selector!("owner_of"),
+            2,
+            reverted("Ownable: caller is not the owner"),
+        );
+        assert!(matches!(
+            owner_of(&client, CONTRACT, token_id(), BLOCK).await,
+            Err(OwnershipError::Client(StarknetClientError::Contract(_)))
+        ));
+
+        let client = mock(selector!("other"), 2, Ok(vec![OWNER]));
+        assert!(matches!(
+            owner_of(&client, CONTRACT, token_id(), BLOCK).await,
+            Err(OwnershipError::Client(
+                StarknetClientError::EntrypointNotFound(_)
+            ))
+        ));
+
+        let client = mock(selector!("owner_of"), 2, Ok(vec![OWNER, OWNER]));
+        assert!(matches!(
+            owner_of(&client, CONTRACT, token_id(), BLOCK).await,
+            Err(OwnershipError::InvalidValue(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn balance_of_erc721() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, selector, calldata, _| {
+                assert_eq!(calldata, vec![OWNER]);
+                match selector == selector!("balanceOf") {
+                    true => Ok(vec![felt!("0x3"), FieldElement::ZERO]),
+                    false => Err(StarknetClientError::EntrypointNotFound("".to_string())),
+                }
+            });
+
+        let balance = balance_of(&client, CONTRACT, OWNER, None, BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(balance, CairoU256::from(3_u64));
+    }
+
+    #[tokio::test]
+    async fn balance_of_erc1155() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(|_, _, calldata, _| match calldata[..] {
+                // Cairo 0, the token id as a felt.
+                [owner, id] if owner == OWNER && id == felt!("0x7") => Ok(vec![felt!("0x5")]),
+                _ => Err(StarknetClientError::InputTooLong),
+            });
+
+        let balance = balance_of(&client, CONTRACT, OWNER, Some(token_id()), BLOCK)
+            .await
+            .unwrap();
+        assert_eq!(balance, CairoU256::from(5_u64));
+
+        let client = mock(
+            selector!("balance_of"),
+            3,
+            Ok(vec![felt!("0x5"), felt!("0x1")]),
+        );
+        let balance = balance_of(&client, CONTRACT, OWNER, Some(token_id()), BLOCK)
+            .await
+            .unwrap();
+        assert_eq!(balance, CairoU256::new(5, 1));
+
+        let client = mock(
+            selector!("balance_of"),
+            3,
+            reverted("ERC1155: invalid token ID"),
+        );
+        assert!(matches!(
+            balance_of(&client, CONTRACT, OWNER, Some(token_id()), BLOCK).await,
+            Err(OwnershipError::TokenNotFound { .. })
+        ));
+
+        // Without token id, the reason is not about a token.
+        let client = mock(
+            selector!("balance_of"),
+            3,
+            reverted("ERC1155: invalid token ID"),
+        );
+        assert!(matches!(
+            balance_of(&client, CONTRACT, OWNER, None, BLOCK).await,
+            Err(OwnershipError::Client(_))
+        ));
+
+        let two_pow_128 = FieldElement::from(u128::MAX) + FieldElement::ONE;
+        let client = mock(
+            selector!("balance_of"),
+            3,
+            Ok(vec![two_pow_128, felt!("0x0")]),
+        );
+        assert!(matches!(
+            balance_of(&client, CONTRACT, OWNER, Some(token_id()), BLOCK).await,
+            Err(OwnershipError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn nonexistent_token_reasons() {
+        assert!(is_nonexistent_token("ERC721: nonexistent token"));
+        assert!(is_nonexistent_token(
+            "Execution failed. Failure reason: 0x4552433732313a206e6f6e6578697374656e7420746f6b656e."
+        ));
+        assert!(!is_nonexistent_token("ERC721: unauthorized caller"));
+        assert!(!is_nonexistent_token("0x0"));
+    }
+}
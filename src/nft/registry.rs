@@ -0,0 +1,349 @@
+//! Registry of known class hashes, to classify contracts deployed from
+//! well-known classes (OpenZeppelin, ArkProject...) with a single
+//! `get_class_hash_at` instead of probing their entrypoints.
+//!
+//! The registry can be loaded from a TOML or JSON file:
+//!
+//! ```toml
+//! [[classes]]
+//! class_hash = "0x..."
+//! standard = "erc721"
+//! layout = "cairo1"
+//! name = "OpenZeppelin ERC721"
+//! ```
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{BlockId, FieldElement};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+use super::contract_type::{
+    detect_contract_type, ContractType, ContractTypeDetection, DetectionConfidence,
+    DetectionEvidence,
+};
+use super::events::EventLayout;
+use crate::starknet_utils::client::{StarknetClient, StarknetClientError};
+use crate::starknet_utils::serialization::felt_hex;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid TOML registry: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Can't serialize registry to TOML: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("Invalid JSON registry: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported registry file format: {0}")]
+    UnsupportedFormat(String),
+}
+
+/// What is known about a class.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownClass {
+    #[serde(with = "felt_hex")]
+    pub class_hash: FieldElement,
+    pub standard: ContractType,
+    /// Layout of the events of the class, used to decode them.
+    pub layout: EventLayout,
+    /// Human readable name of the class, like `OpenZeppelin ERC721 v0.8.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Content of a registry file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    classes: Vec<KnownClass>,
+}
+
+/// Known classes by class hash. The registry can be shared between tasks
+/// and updated while in use.
+#[derive(Debug, Default)]
+pub struct ClassHashRegistry {
+    classes: RwLock<HashMap<FieldElement, KnownClass>>,
+}
+
+impl ClassHashRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a registry from a `.toml` or `.json` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let registry = Self::new();
+        registry.load_file(path)?;
+        Ok(registry)
+    }
+
+    /// Adds the classes of a `.toml` or `.json` file to the registry,
+    /// replacing the classes already registered with the same class hash.
+    /// Returns the number of classes loaded.
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<usize, RegistryError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+
+        let file: RegistryFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&content)?,
+            Some("json") => serde_json::from_str(&content)?,
+            _ => return Err(RegistryError::UnsupportedFormat(path.display().to_string())),
+        };
+
+        let count = file.classes.len();
+        file.classes.into_iter().for_each(|c| self.insert(c));
+
+        Ok(count)
+    }
+
+    /// Writes the registry to a `.toml` or `.json` file.
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), RegistryError> {
+        let path = path.as_ref();
+
+        let mut classes = self.classes();
+        classes.sort_by_key(|c| c.class_hash);
+        let file = RegistryFile { classes };
+
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::to_string_pretty(&file)?,
+            Some("json") => serde_json::to_string_pretty(&file)?,
+            _ => return Err(RegistryError::UnsupportedFormat(path.display().to_string())),
+        };
+
+        Ok(std::fs::write(path, content)?)
+    }
+
+    /// Registers a class, replacing the previous entry for the same class hash.
+    pub fn insert(&self, class: KnownClass) {
+        self.write().insert(class.class_hash, class);
+    }
+
+    /// Removes a class from the registry.
+    pub fn remove(&self, class_hash: &FieldElement) -> Option<KnownClass> {
+        self.write().remove(class_hash)
+    }
+
+    pub fn get(&self, class_hash: &FieldElement) -> Option<KnownClass> {
+        self.read().get(class_hash).cloned()
+    }
+
+    /// Returns all the registered classes.
+    pub fn classes(&self) -> Vec<KnownClass> {
+        self.read().values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Returns the detection for a registered class hash.
+    pub fn detection(&self, class_hash: &FieldElement) -> Option<ContractTypeDetection> {
+        self.get(class_hash).map(|class| ContractTypeDetection {
+            contract_type: class.standard,
+            confidence: DetectionConfidence::High,
+            evidence: vec![DetectionEvidence::KnownClassHash {
+                class_hash: class.class_hash,
+            }],
+            layout: Some(class.layout),
+        })
+    }
+
+    // A panic while holding the lock can't leave the map in an
+    // inconsistent state, poisoning is ignored.
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<FieldElement, KnownClass>> {
+        self.classes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<FieldElement, KnownClass>> {
+        self.classes.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Detects the standard of the contract, with a single `get_class_hash_at`
+/// if its class is registered, or by probing the contract otherwise.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `registry` - The known classes.
+/// * `address` - The address of the contract.
+/// * `block` - The block at which the contract is called.
+pub async fn classify_contract<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    registry: &ClassHashRegistry,
+    address: FieldElement,
+    block: BlockId,
+) -> Result<ContractTypeDetection, StarknetClientError> {
+    let class_hash = client.class_hash_at(address, block).await?;
+
+    match registry.detection(&class_hash) {
+        Some(detection) => Ok(detection),
+        None => detect_contract_type(client, address, block).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::macros::felt;
+    use std::path::PathBuf;
+
+    const ERC721_CLASS: FieldElement = felt!("0xc1a55");
+    const ERC1155_CLASS: FieldElement = felt!("0xc1a56");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    fn registry() -> ClassHashRegistry {
+        let registry = ClassHashRegistry::new();
+        registry.insert(KnownClass {
+            class_hash: ERC721_CLASS,
+            standard: ContractType::ERC721,
+            layout: EventLayout::Cairo1,
+            name: Some("OpenZeppelin ERC721".to_string()),
+        });
+        registry.insert(KnownClass {
+            class_hash: ERC1155_CLASS,
+            standard: ContractType::ERC1155,
+            layout: EventLayout::Cairo0,
+            name: None,
+        });
+        registry
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn file_round_trip() {
+        for (name, expected) in [
+            ("registry.toml", r#"class_hash = "0xc1a55""#),
+            ("registry.json", r#""class_hash": "0xc1a55""#),
+        ] {
+            let path = temp_path(name);
+            registry().save_file(&path).unwrap();
+
+            let content = std::fs::read_to_string(&path).unwrap();
+            assert!(content.contains(expected), "{}", content);
+
+            let loaded = ClassHashRegistry::from_file(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let mut classes = loaded.classes();
+            classes.sort_by_key(|c| c.class_hash);
+            let mut expected = registry().classes();
+            expected.sort_by_key(|c| c.class_hash);
+            assert_eq!(classes, expected);
+        }
+    }
+
+    #[test]
+    fn load_file_replaces_registered_classes() {
+        let path = temp_path("replace.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[classes]]
+            class_hash = "0xc1a55"
+            standard = "erc1155"
+            layout = "cairo0"
+            "#,
+        )
+        .unwrap();
+
+        let registry = registry();
+        assert_eq!(registry.load_file(&path).unwrap(), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(registry.len(), 2);
+        let class = registry.get(&ERC721_CLASS).unwrap();
+        assert_eq!(class.standard, ContractType::ERC1155);
+        assert_eq!(class.name, None);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let path = temp_path("registry.yaml");
+        std::fs::write(&path, "classes: []").unwrap();
+        assert!(matches!(
+            ClassHashRegistry::from_file(&path),
+            Err(RegistryError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            registry().save_file(&path),
+            Err(RegistryError::UnsupportedFormat(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        for (name, content) in [
+            ("invalid.toml", "[[classes]]\nclass_hash = \"0x1\"\n"),
+            (
+                "invalid.json",
+                r#"{"classes": [{"class_hash": "0x1", "standard": "erc42"}]}"#,
+            ),
+        ] {
+            let path = temp_path(name);
+            std::fs::write(&path, content).unwrap();
+            let result = ClassHashRegistry::from_file(&path);
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(matches!(
+                result,
+                Err(RegistryError::Toml(_)) | Err(RegistryError::Json(_))
+            ));
+        }
+
+        assert!(matches!(
+            ClassHashRegistry::from_file(temp_path("missing.toml")),
+            Err(RegistryError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn classify_contract_registered_class() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_class_hash_at()
+            .returning(|_, _| Ok(ERC1155_CLASS));
+        // No probing call is expected.
+        client.expect_call_contract().never();
+
+        let detection = classify_contract(&client, &registry(), felt!("0x123"), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(detection.contract_type, ContractType::ERC1155);
+        assert_eq!(detection.confidence, DetectionConfidence::High);
+        assert_eq!(detection.layout, Some(EventLayout::Cairo0));
+        assert_eq!(
+            detection.evidence,
+            vec![DetectionEvidence::KnownClassHash {
+                class_hash: ERC1155_CLASS
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn classify_contract_unknown_class() {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_class_hash_at()
+            .returning(|_, _| Ok(felt!("0x999")));
+        client
+            .expect_call_contract()
+            .returning(|_, _, _, _| Err(StarknetClientError::EntrypointNotFound("".to_string())));
+
+        let detection = classify_contract(&client, &registry(), felt!("0x123"), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(detection.contract_type, ContractType::Other);
+        assert_eq!(detection.layout, None);
+    }
+}
@@ -0,0 +1,387 @@
+//! ERC-2981 royalty info queries.
+use starknet::core::types::{BlockId, FieldElement};
+use starknet::macros::{felt, selector};
+
+use super::classifier::PaymentTransfer;
+use super::contract_type::supports_interface;
+use super::token_uri::call_with_token_id;
+use crate::starknet_utils::client::{StarknetClient, StarknetClientError};
+use crate::starknet_utils::CairoU256;
+
+/// SRC5 interface id of ERC-2981.
+pub const IERC2981_ID: FieldElement =
+    felt!("0x2d3414e45a8700c29f119a54b9f11dca0e29e06ddcb214018fc37340e165ed6");
+/// ERC-165 interface id of ERC-2981, used by older contracts.
+pub const IERC2981_ID_LEGACY: FieldElement = felt!("0x2a55205a");
+
+pub const ROYALTY_INFO_SELECTORS: [FieldElement; 2] =
+    [selector!("royalty_info"), selector!("royaltyInfo")];
+
+/// Denominator of royalties expressed in basis points.
+const BASIS_POINTS: u64 = 10_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoyaltyError {
+    #[error("Contract {0:#x} does not support ERC-2981")]
+    NotSupported(FieldElement),
+    #[error("Call failed: {0}")]
+    Client(#[from] StarknetClientError),
+    #[error("Invalid value returned: {0}")]
+    InvalidValue(String),
+}
+
+/// Royalty declared by a collection for a sale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoyaltyInfo {
+    pub receiver: FieldElement,
+    /// Royalty amount for `sale_price`.
+    pub amount: CairoU256,
+    pub sale_price: CairoU256,
+}
+
+impl RoyaltyInfo {
+    /// Returns the royalty rate in basis points, rounded down.
+    /// Returns `None` if the sale price is zero or on overflow.
+    pub fn basis_points(&self) -> Option<CairoU256> {
+        self.amount
+            .checked_mul(CairoU256::from(BASIS_POINTS))?
+            .checked_div(self.sale_price)
+    }
+
+    /// Returns the royalty for another sale price, with the same rate.
+    /// Returns `None` if the sale price is zero or on overflow.
+    pub fn amount_for(&self, sale_price: CairoU256) -> Option<CairoU256> {
+        self.amount
+            .checked_mul(sale_price)?
+            .checked_div(self.sale_price)
+    }
+
+    /// Returns the total paid to the receiver in the given currency.
+    pub fn paid_amount(
+        &self,
+        payments: &[PaymentTransfer],
+        currency: FieldElement,
+    ) -> Option<CairoU256> {
+        payments
+            .iter()
+            .filter(|p| p.currency == currency && p.to == self.receiver)
+            .try_fold(CairoU256::ZERO, |total, p| total.checked_add(p.amount))
+    }
+
+    /// Returns true if the payments to the receiver cover the royalty.
+    pub fn is_paid(&self, payments: &[PaymentTransfer], currency: FieldElement) -> bool {
+        self.paid_amount(payments, currency)
+            .is_some_and(|paid| paid >= self.amount)
+    }
+}
+
+/// Returns the royalty declared by the collection for a sale of the token.
+/// Returns `RoyaltyError::NotSupported` if the contract declares through SRC5
+/// that ERC-2981 is not supported, or if `royalty_info` does not exist.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `contract` - The address of the collection contract.
+/// * `token_id` - The id of the token.
+/// * `sale_price` - The price of the sale.
+/// * `block` - The block at which the contract is called.
+pub async fn royalty_info<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract: FieldElement,
+    token_id: CairoU256,
+    sale_price: CairoU256,
+    block: BlockId,
+) -> Result<RoyaltyInfo, RoyaltyError> {
+    if !supports_erc2981(client, contract, block).await? {
+        return Err(RoyaltyError::NotSupported(contract));
+    }
+
+    let sale_price_felts = sale_price.to_felts();
+
+    let felts = match call_with_token_id(
+        client,
+        contract,
+        &ROYALTY_INFO_SELECTORS,
+        token_id,
+        block,
+        |id| [id, &sale_price_felts].concat(),
+    )
+    .await
+    {
+        Ok((felts, _)) => felts,
+        Err(StarknetClientError::EntrypointNotFound(_)) => {
+            return Err(RoyaltyError::NotSupported(contract))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let amount = match felts[..] {
+        [_, amount] => CairoU256::from(amount),
+        [_, low, high] => CairoU256::from_felts(low, high)
+            .map_err(|e| RoyaltyError::InvalidValue(e.to_string()))?,
+        _ => {
+            return Err(RoyaltyError::InvalidValue(format!(
+                "unexpected royalty info length {}",
+                felts.len()
+            )))
+        }
+    };
+
+    Ok(RoyaltyInfo {
+        receiver: felts[0],
+        amount,
+        sale_price,
+    })
+}
+
+/// Returns false if the contract declares through SRC5 that ERC-2981 is
+/// not supported. Contracts without SRC5 are assumed to support it, the
+/// `royalty_info` call tells if they do.
+async fn supports_erc2981<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract: FieldElement,
+    block: BlockId,
+) -> Result<bool, StarknetClientError> {
+    for interface_id in [IERC2981_ID, IERC2981_ID_LEGACY] {
+        match supports_interface(client, contract, interface_id, block).await {
+            Ok(true) => return Ok(true),
+            Ok(false) => (),
+            Err(StarknetClientError::EntrypointNotFound(_))
+            | Err(StarknetClientError::Contract(_))
+            | Err(StarknetClientError::InputTooShort)
+            | Err(StarknetClientError::InputTooLong) => return Ok(true),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nft::contract_type::SUPPORTS_INTERFACE_SELECTORS;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::providers::ProviderError;
+
+    const CONTRACT: FieldElement = felt!("0xc011");
+    const RECEIVER: FieldElement = felt!("0x2ec");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    /// How the contract answers `supports_interface`.
+    #[derive(Clone, Copy)]
+    enum Src5 {
+        Missing,
+        Supports(FieldElement),
+        Failing,
+    }
+
+    /// A collection with the given SRC5 support, answering `royalty_info`
+    /// with `result` when called with `calldata_len` felts.
+    fn mock(
+        src5: Src5,
+        calldata_len: usize,
+        result: Option<Vec<FieldElement>>,
+    ) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(move |_, selector, calldata, _| {
+                if SUPPORTS_INTERFACE_SELECTORS.contains(&selector) {
+                    return match src5 {
+                        Src5::Missing => {
+                            Err(StarknetClientError::EntrypointNotFound("".to_string()))
+                        }
+                        Src5::Supports(id) => {
+                            Ok(vec![FieldElement::from((calldata == vec![id]) as u8)])
+                        }
+                        Src5::Failing => {
+                            Err(StarknetClientError::Provider(ProviderError::RateLimited))
+                        }
+                    };
+                }
+
+                match (&result, selector == selector!("royalty_info")) {
+                    (Some(result), true) if calldata.len() == calldata_len => Ok(result.clone()),
+                    (Some(_), true) => Err(StarknetClientError::InputTooLong),
+                    _ => Err(StarknetClientError::EntrypointNotFound("".to_string())),
+                }
+            });
+        client
+    }
+
+    fn price() -> CairoU256 {
+        CairoU256::from(1000_u64)
+    }
+
+    async fn royalty(client: &MockStarknetClient) -> Result<RoyaltyInfo, RoyaltyError> {
+        royalty_info(client, CONTRACT, CairoU256::ONE, price(), BLOCK).await
+    }
+
+    #[tokio::test]
+    async fn royalty_info_cairo1() {
+        // `[receiver, amount_low, amount_high]` for a u256 token id and price.
+        let client = mock(
+            Src5::Supports(IERC2981_ID),
+            4,
+            Some(vec![RECEIVER, felt!("0x32"), felt!("0x0")]),
+        );
+
+        let royalty = royalty(&client).await.unwrap();
+
+        assert_eq!(
+            royalty,
+            RoyaltyInfo {
+                receiver: RECEIVER,
+                amount: CairoU256::from(50_u64),
+                sale_price: price(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn royalty_info_cairo0() {
+        // `[receiver, amount]` for a felt token id, without SRC5.
+        let client = mock(Src5::Missing, 3, Some(vec![RECEIVER, felt!("0x32")]));
+
+        let royalty = royalty(&client).await.unwrap();
+
+        assert_eq!(royalty.receiver, RECEIVER);
+        assert_eq!(royalty.amount, CairoU256::from(50_u64));
+    }
+
+    #[tokio::test]
+    async fn royalty_info_legacy_interface_id() {
+        let client = mock(
+            Src5::Supports(IERC2981_ID_LEGACY),
+            4,
+            Some(vec![RECEIVER, felt!("0x32"), felt!("0x0")]),
+        );
+
+        assert!(royalty(&client).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn royalty_info_not_supported() {
+        // Declared as not supported through SRC5, `royalty_info` is not called.
+        let client = mock(
+            Src5::Supports(felt!("0x1")),
+            4,
+            Some(vec![RECEIVER, felt!("0x32"), felt!("0x0")]),
+        );
+        assert!(matches!(
+            royalty(&client).await,
+            Err(RoyaltyError::NotSupported(c)) if c == CONTRACT
+        ));
+
+        // Without SRC5 nor `royalty_info`.
+        let client = mock(Src5::Missing, 4, None);
+        assert!(matches!(
+            royalty(&client).await,
+            Err(RoyaltyError::NotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn royalty_info_errors() {
+        let client = mock(Src5::Failing, 4, Some(vec![RECEIVER, felt!("0x32")]));
+        assert!(matches!(
+            royalty(&client).await,
+            Err(RoyaltyError::Client(StarknetClientError::Provider(_)))
+        ));
+
+        let client = mock(Src5::Missing, 4, Some(vec![RECEIVER]));
+        assert!(matches!(
+            royalty(&client).await,
+            Err(RoyaltyError::InvalidValue(_))
+        ));
+
+        let two_pow_128 = FieldElement::from(u128::MAX) + FieldElement::ONE;
+        let client = mock(
+            Src5::Missing,
+            4,
+            Some(vec![RECEIVER, two_pow_128, felt!("0x0")]),
+        );
+        assert!(matches!(
+            royalty(&client).await,
+            Err(RoyaltyError::InvalidValue(_))
+        ));
+    }
+
+    fn info(amount: u64, sale_price: u64) -> RoyaltyInfo {
+        RoyaltyInfo {
+            receiver: RECEIVER,
+            amount: CairoU256::from(amount),
+            sale_price: CairoU256::from(sale_price),
+        }
+    }
+
+    #[test]
+    fn basis_points() {
+        assert_eq!(
+            info(50, 1000).basis_points(),
+            Some(CairoU256::from(500_u64))
+        );
+        // 3.33%, rounded down.
+        assert_eq!(info(1, 3).basis_points(), Some(CairoU256::from(3333_u64)));
+        assert_eq!(info(0, 1000).basis_points(), Some(CairoU256::ZERO));
+        assert_eq!(info(50, 0).basis_points(), None);
+
+        let overflow = RoyaltyInfo {
+            amount: CairoU256::MAX,
+            ..info(0, 1)
+        };
+        assert_eq!(overflow.basis_points(), None);
+    }
+
+    #[test]
+    fn amount_for() {
+        let royalty = info(50, 1000);
+        assert_eq!(
+            royalty.amount_for(CairoU256::from(2000_u64)),
+            Some(CairoU256::from(100_u64))
+        );
+        assert_eq!(
+            royalty.amount_for(CairoU256::from(19_u64)),
+            Some(CairoU256::ZERO)
+        );
+        assert_eq!(royalty.amount_for(CairoU256::MAX), None);
+        assert_eq!(info(50, 0).amount_for(CairoU256::ONE), None);
+    }
+
+    #[test]
+    fn is_paid() {
+        let eth = felt!("0xe7");
+        let payment = |currency, to, amount: u64| PaymentTransfer {
+            currency,
+            from: felt!("0xb0b"),
+            to,
+            amount: CairoU256::from(amount),
+        };
+        let royalty = info(50, 1000);
+
+        let payments = [payment(eth, RECEIVER, 20), payment(eth, RECEIVER, 30)];
+        assert_eq!(
+            royalty.paid_amount(&payments, eth),
+            Some(CairoU256::from(50_u64))
+        );
+        assert!(royalty.is_paid(&payments, eth));
+
+        // Underpaid, paid in another currency, or to another account.
+        assert!(!royalty.is_paid(&payments[..1], eth));
+        assert!(!royalty.is_paid(&payments, felt!("0x5714")));
+        assert!(!royalty.is_paid(&[payment(eth, felt!("0x5e11"), 50)], eth));
+
+        let overflow = [
+            PaymentTransfer {
+                amount: CairoU256::MAX,
+                ..payment(eth, RECEIVER, 0)
+            },
+            payment(eth, RECEIVER, 1),
+        ];
+        assert_eq!(royalty.paid_amount(&overflow, eth), None);
+        assert!(!royalty.is_paid(&overflow, eth));
+    }
+}
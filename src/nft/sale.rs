@@ -0,0 +1,354 @@
+//! Detection of NFT sales: token transfers paired with payments of ERC-20
+//! payment tokens in the same transaction, optionally attributed to a
+//! marketplace from the orderbook events it emits.
+use serde::{Deserialize, Serialize};
+use starknet::core::types::{BlockId, EmittedEvent, FieldElement};
+use starknet::core::utils::{get_selector_from_name, NonAsciiNameError};
+use std::collections::HashMap;
+
+use super::classifier::{ClassifiedTransfer, PaymentTransfer, TokenEventKind, TransferClassifier};
+use super::contract_type::ContractTypeDetection;
+use super::events::{TRANSFER_BATCH_SELECTOR, TRANSFER_SELECTOR, TRANSFER_SINGLE_SELECTOR};
+use super::registry::{classify_contract, ClassHashRegistry};
+use crate::starknet_utils::client::{StarknetClient, StarknetClientError};
+use crate::starknet_utils::CairoU256;
+
+/// Identifies the orderbook events of a marketplace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketplaceSignature {
+    pub name: String,
+    /// Address of the marketplace contract. If `None`, the events
+    /// are matched whatever their emitter.
+    #[serde(default)]
+    pub address: Option<FieldElement>,
+    /// Selectors of the events emitted when an order is fulfilled.
+    pub event_selectors: Vec<FieldElement>,
+}
+
+impl MarketplaceSignature {
+    /// Creates a signature from the names of the events, like `OrderFulfilled`.
+    pub fn from_event_names(
+        name: &str,
+        address: Option<FieldElement>,
+        event_names: &[&str],
+    ) -> Result<Self, NonAsciiNameError> {
+        Ok(Self {
+            name: name.to_string(),
+            address,
+            event_selectors: event_names
+                .iter()
+                .map(|n| get_selector_from_name(n))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns true if the event is an orderbook event of this marketplace.
+    pub fn matches(&self, event: &EmittedEvent) -> bool {
+        self.address.is_none_or(|a| a == event.from_address)
+            && event
+                .keys
+                .first()
+                .is_some_and(|k| self.event_selectors.contains(k))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sale {
+    pub collection: FieldElement,
+    pub token_id: CairoU256,
+    pub seller: FieldElement,
+    pub buyer: FieldElement,
+    /// Share of `total_price` for this token. When several tokens are bought
+    /// from the same seller in the same transaction, the total is split evenly
+    /// between them, rounded down: the shares may add up to less than the total.
+    pub price: CairoU256,
+    /// Amount paid by the buyer to the seller in the transaction.
+    pub total_price: CairoU256,
+    /// Address of the payment token.
+    pub currency: FieldElement,
+    /// Name of the marketplace, if one of the known signatures matched.
+    pub marketplace: Option<String>,
+    pub transaction_hash: FieldElement,
+    pub block_number: Option<u64>,
+}
+
+/// Detects sales in the events of a transaction.
+#[derive(Debug, Clone, Default)]
+pub struct SaleDetector {
+    pub classifier: TransferClassifier,
+    pub marketplaces: Vec<MarketplaceSignature>,
+    /// If true, transfers with payments are only sales when the transaction
+    /// contains an event of a known marketplace.
+    pub require_marketplace: bool,
+}
+
+impl SaleDetector {
+    pub fn new(
+        classifier: TransferClassifier,
+        marketplaces: Vec<MarketplaceSignature>,
+        require_marketplace: bool,
+    ) -> Self {
+        Self {
+            classifier,
+            marketplaces,
+            require_marketplace,
+        }
+    }
+
+    /// Returns the sales found in the events of one transaction.
+    ///
+    /// The price is the amount paid by the buyer to the seller, the transaction
+    /// fee excluded. When several tokens are bought from the same seller in the
+    /// same transaction, the amount is split evenly between them, rounded down.
+    /// Transfers paid in several currencies can't be priced, and are not
+    /// reported as sales.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - All the events of the transaction, as returned by
+    ///   `events_from_tx_receipt` without keys filter.
+    /// * `detections` - Standards of the contracts emitting the events.
+    pub fn detect_sales(
+        &self,
+        events: &[EmittedEvent],
+        detections: &HashMap<FieldElement, ContractTypeDetection>,
+    ) -> Vec<Sale> {
+        let marketplace = self
+            .marketplaces
+            .iter()
+            .find(|m| events.iter().any(|e| m.matches(e)))
+            .map(|m| m.name.clone());
+
+        if self.require_marketplace && marketplace.is_none() {
+            return vec![];
+        }
+
+        let classified = self.classifier.classify_transaction(events, detections);
+        let sales: Vec<(&ClassifiedTransfer, &[PaymentTransfer])> = classified
+            .iter()
+            .filter_map(|c| match &c.kind {
+                TokenEventKind::Sale { payments } => Some((c, payments.as_slice())),
+                _ => None,
+            })
+            .collect();
+
+        sales
+            .iter()
+            .filter_map(|(classified, payments)| {
+                let transfer = &classified.transfer;
+                let (buyer, seller) = (transfer.to, transfer.from);
+
+                let count = sales
+                    .iter()
+                    .filter(|(c, _)| c.transfer.to == buyer && c.transfer.from == seller)
+                    .count();
+                let (currency, total) = sum_payments(payments)?;
+
+                Some(Sale {
+                    collection: transfer.contract_address,
+                    token_id: transfer.token_id,
+                    seller,
+                    buyer,
+                    price: total.checked_div(CairoU256::from(count as u64))?,
+                    total_price: total,
+                    currency,
+                    marketplace: marketplace.clone(),
+                    transaction_hash: transfer.transaction_hash,
+                    block_number: transfer.block_number,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns the sales of all the transactions of a block.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to fetch the receipts.
+/// * `detector` - The sale detector.
+/// * `registry` - Known classes, checked before probing the emitters of transfers.
+/// * `block` - The block to scan.
+pub async fn detect_block_sales<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    detector: &SaleDetector,
+    registry: &ClassHashRegistry,
+    block: BlockId,
+) -> Result<Vec<Sale>, StarknetClientError> {
+    let (_, tx_hashes) = client.block_txs_hashes(block).await?;
+
+    let mut detections = HashMap::new();
+    let mut sales = vec![];
+    for tx_hash in tx_hashes {
+        let events = client.events_from_tx_receipt(tx_hash, None).await?;
+
+        for event in &events {
+            let is_transfer = event.keys.first().is_some_and(|k| {
+                [
+                    TRANSFER_SELECTOR,
+                    TRANSFER_SINGLE_SELECTOR,
+                    TRANSFER_BATCH_SELECTOR,
+                ]
+                .contains(k)
+            });
+            if !is_transfer
+                || detections.contains_key(&event.from_address)
+                || detector
+                    .classifier
+                    .payment_tokens
+                    .contains(&event.from_address)
+            {
+                continue;
+            }
+
+            let detection = classify_contract(client, registry, event.from_address, block).await?;
+            detections.insert(event.from_address, detection);
+        }
+
+        sales.extend(detector.detect_sales(&events, &detections));
+    }
+
+    Ok(sales)
+}
+
+/// Sums the payments, returning their currency with the total.
+/// Returns `None` if there is no payment, if they are in several
+/// currencies, or if the sum overflows.
+fn sum_payments(payments: &[PaymentTransfer]) -> Option<(FieldElement, CairoU256)> {
+    let currency = payments.first()?.currency;
+    if payments.iter().any(|p| p.currency != currency) {
+        return None;
+    }
+
+    let total = payments
+        .iter()
+        .try_fold(CairoU256::ZERO, |total, p| total.checked_add(p.amount))?;
+
+    Some((currency, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nft::classifier::{ETH_ADDRESS, STRK_ADDRESS};
+    use crate::nft::contract_type::ContractType;
+    use crate::nft::events::EventLayout;
+    use crate::nft::registry::KnownClass;
+    use crate::nft::test_fixtures::*;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::macros::felt;
+
+    #[test]
+    fn fee_is_not_in_the_price() {
+        let events = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment(BUYER, SELLER, 100),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+
+        let sales = SaleDetector::default().detect_sales(&events, &detections());
+
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].seller, SELLER);
+        assert_eq!(sales[0].buyer, BUYER);
+        assert_eq!(sales[0].price, CairoU256::from(100u64));
+        assert_eq!(sales[0].total_price, CairoU256::from(100u64));
+        assert_eq!(sales[0].currency, ETH_ADDRESS);
+    }
+
+    #[test]
+    fn bundle_price_is_split_rounded_down() {
+        let mut second = transfer(COLLECTION, SELLER, BUYER);
+        second.data[2] = felt!("0x8");
+        let events = [
+            transfer(COLLECTION, SELLER, BUYER),
+            second,
+            payment(BUYER, SELLER, 101),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+
+        let sales = SaleDetector::default().detect_sales(&events, &detections());
+
+        assert_eq!(sales.len(), 2);
+        for sale in &sales {
+            assert_eq!(sale.price, CairoU256::from(50u64));
+            assert_eq!(sale.total_price, CairoU256::from(101u64));
+        }
+    }
+
+    #[test]
+    fn payments_in_several_currencies_are_not_priced() {
+        let strk = STRK_ADDRESS;
+        let detector = SaleDetector::default();
+
+        let events = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment(BUYER, SELLER, 100),
+            payment_in(strk, BUYER, SELLER, 200),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+        assert!(detector.detect_sales(&events, &detections()).is_empty());
+
+        let events = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment_in(strk, BUYER, SELLER, 200),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+        let sales = detector.detect_sales(&events, &detections());
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].currency, strk);
+        assert_eq!(sales[0].price, CairoU256::from(200u64));
+    }
+
+    #[test]
+    fn transfer_paying_only_the_fee_is_not_a_sale() {
+        let events = [
+            transfer(COLLECTION, SELLER, BUYER),
+            payment(SELLER, SEQUENCER, 10),
+        ];
+
+        let sales = SaleDetector::default().detect_sales(&events, &detections());
+
+        assert!(sales.is_empty());
+    }
+
+    #[tokio::test]
+    async fn erc20_swap_is_not_a_sale() {
+        // The seller sends an ERC-20 to the buyer, which pays in ETH.
+        let events = vec![
+            transfer(ERC20, SELLER, BUYER),
+            payment(BUYER, SELLER, 100),
+            payment(BUYER, SEQUENCER, 10),
+        ];
+
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_block_txs_hashes()
+            .returning(|_| Ok((0, vec![TX_HASH])));
+        client
+            .expect_events_from_tx_receipt()
+            .returning(move |_, _| Ok(events.clone()));
+        client
+            .expect_class_hash_at()
+            .returning(|address, _| Ok(address));
+
+        let registry = ClassHashRegistry::new();
+        registry.insert(KnownClass {
+            class_hash: ERC20,
+            standard: ContractType::ERC20,
+            layout: EventLayout::Cairo0,
+            name: None,
+        });
+
+        let sales = detect_block_sales(
+            &client,
+            &SaleDetector::default(),
+            &registry,
+            BlockId::Number(1),
+        )
+        .await
+        .unwrap();
+
+        assert!(sales.is_empty());
+    }
+}
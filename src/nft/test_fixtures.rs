@@ -0,0 +1,67 @@
+//! Events and detections shared by the tests of the transfer classifier
+//! and of the sale detector.
+use starknet::core::types::{EmittedEvent, FieldElement};
+use starknet::macros::felt;
+use std::collections::HashMap;
+
+use super::classifier::ETH_ADDRESS;
+use super::contract_type::{ContractType, ContractTypeDetection, DetectionConfidence};
+use super::events::TRANSFER_SELECTOR;
+
+pub const COLLECTION: FieldElement = felt!("0xc011");
+pub const ERC20: FieldElement = felt!("0xe2c20");
+pub const SELLER: FieldElement = felt!("0x5e11");
+pub const BUYER: FieldElement = felt!("0xb0b");
+pub const SEQUENCER: FieldElement = felt!("0x5e0");
+pub const TX_HASH: FieldElement = felt!("0x1");
+
+pub fn event(from_address: FieldElement, data: Vec<FieldElement>) -> EmittedEvent {
+    EmittedEvent {
+        from_address,
+        keys: vec![TRANSFER_SELECTOR],
+        data,
+        block_hash: None,
+        block_number: None,
+        transaction_hash: TX_HASH,
+    }
+}
+
+/// Cairo 0 `Transfer`, the last field being a token id or an ERC-20 amount.
+pub fn transfer(contract: FieldElement, from: FieldElement, to: FieldElement) -> EmittedEvent {
+    event(contract, vec![from, to, felt!("0x7")])
+}
+
+/// `Transfer` of an ETH amount.
+pub fn payment(from: FieldElement, to: FieldElement, amount: u64) -> EmittedEvent {
+    payment_in(ETH_ADDRESS, from, to, amount)
+}
+
+/// `Transfer` of an amount of the given ERC-20.
+pub fn payment_in(
+    currency: FieldElement,
+    from: FieldElement,
+    to: FieldElement,
+    amount: u64,
+) -> EmittedEvent {
+    event(
+        currency,
+        vec![from, to, FieldElement::from(amount), FieldElement::ZERO],
+    )
+}
+
+pub fn detection(contract_type: ContractType) -> ContractTypeDetection {
+    ContractTypeDetection {
+        contract_type,
+        confidence: DetectionConfidence::High,
+        evidence: vec![],
+        layout: None,
+    }
+}
+
+/// `COLLECTION` as an ERC-721 and `ERC20` as an ERC-20.
+pub fn detections() -> HashMap<FieldElement, ContractTypeDetection> {
+    HashMap::from([
+        (COLLECTION, detection(ContractType::ERC721)),
+        (ERC20, detection(ContractType::ERC20)),
+    ])
+}
@@ -0,0 +1,431 @@
+//! Resolution of token URIs, whatever the Cairo string encoding used
+//! by the contract.
+use starknet::core::types::{BlockId, FieldElement};
+use starknet::macros::selector;
+
+use crate::starknet_utils::cairo_string_parser::{
+    parse_cairo_string_with_encoding, CairoStringEncoding, ParseError,
+};
+use crate::starknet_utils::client::{call_contract_selectors, StarknetClient, StarknetClientError};
+use crate::starknet_utils::CairoU256;
+
+/// `uri` is the ERC-1155 version of `token_uri`.
+pub const URI_SELECTORS: [FieldElement; 4] = [
+    selector!("token_uri"),
+    selector!("tokenURI"),
+    selector!("tokenUri"),
+    selector!("uri"),
+];
+pub const BASE_URI_SELECTORS: [FieldElement; 4] = [
+    selector!("base_uri"),
+    selector!("baseURI"),
+    selector!("baseUri"),
+    selector!("base_token_uri"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenUriError {
+    #[error("Call failed: {0}")]
+    Client(#[from] StarknetClientError),
+    #[error("Can't parse token uri: {0:?}")]
+    Parse(ParseError),
+}
+
+/// How the token URI was encoded by the contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenUriEncoding {
+    ShortString,
+    LegacyLongString,
+    ByteArray,
+    /// The contract only exposes a base URI, the token id in decimal
+    /// is appended to it.
+    BaseUriWithTokenId,
+}
+
+impl From<CairoStringEncoding> for TokenUriEncoding {
+    fn from(value: CairoStringEncoding) -> Self {
+        match value {
+            CairoStringEncoding::ShortString => TokenUriEncoding::ShortString,
+            CairoStringEncoding::LegacyLongString => TokenUriEncoding::LegacyLongString,
+            CairoStringEncoding::ByteArray => TokenUriEncoding::ByteArray,
+        }
+    }
+}
+
+/// How the token id was passed to the contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenIdCalldata {
+    /// `[low, high]`, as Cairo 1 `u256` and Cairo 0 `Uint256`.
+    U256,
+    /// A single felt.
+    Felt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenUri {
+    pub uri: String,
+    pub encoding: TokenUriEncoding,
+    /// `None` when the URI was built from the base URI.
+    pub token_id_calldata: Option<TokenIdCalldata>,
+}
+
+/// Fetches the URI of a token.
+///
+/// The token id is first passed as a `u256`, then as a felt if the contract
+/// rejects the input as too long. If the contract has no token URI entrypoint
+/// or returns an empty URI, the base URI is used with the token id appended.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `contract` - The address of the collection contract.
+/// * `token_id` - The id of the token.
+/// * `block` - The block at which the contract is called.
+pub async fn fetch_token_uri<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract: FieldElement,
+    token_id: CairoU256,
+    block: BlockId,
+) -> Result<TokenUri, TokenUriError> {
+    let mut empty_uri = None;
+
+    match call_with_token_id(client, contract, &URI_SELECTORS, token_id, block, |id| {
+        id.to_vec()
+    })
+    .await
+    {
+        Ok((felts, token_id_calldata)) => {
+            let (uri, encoding) =
+                parse_cairo_string_with_encoding(&felts).map_err(TokenUriError::Parse)?;
+
+            let token_uri = TokenUri {
+                uri,
+                encoding: encoding.into(),
+                token_id_calldata: Some(token_id_calldata),
+            };
+
+            if !token_uri.uri.is_empty() {
+                return Ok(token_uri);
+            }
+
+            empty_uri = Some(token_uri);
+        }
+        Err(StarknetClientError::EntrypointNotFound(_)) => (),
+        Err(e) => return Err(e.into()),
+    };
+
+    let felts = match call_contract_selectors(client, contract, &BASE_URI_SELECTORS, vec![], block)
+        .await
+    {
+        Ok(felts) => felts,
+        // The token URI is empty, but it's the only information available.
+        Err(e @ StarknetClientError::EntrypointNotFound(_)) => return empty_uri.ok_or(e.into()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (base_uri, _) = parse_cairo_string_with_encoding(&felts).map_err(TokenUriError::Parse)?;
+
+    Ok(TokenUri {
+        uri: format!("{}{}", base_uri, token_id.to_decimal(false)),
+        encoding: TokenUriEncoding::BaseUriWithTokenId,
+        token_id_calldata: None,
+    })
+}
+
+/// Calls the contract trying all the given selectors, with calldata built
+/// around the token id by `calldata`.
+/// The token id is passed as a `u256`, then as a felt if the contract rejects
+/// the input as too long.
+///
+/// # Arguments
+///
+/// * `client` - Starknet client used to call the contract.
+/// * `contract` - The address of the contract.
+/// * `selectors` - The selectors to try, see `call_contract_selectors`.
+/// * `token_id` - The id of the token.
+/// * `block` - The block at which the contract is called.
+/// * `calldata` - Builds the calldata from the serialized token id.
+pub async fn call_with_token_id<C, F>(
+    client: &C,
+    contract: FieldElement,
+    selectors: &[FieldElement],
+    token_id: CairoU256,
+    block: BlockId,
+    calldata: F,
+) -> Result<(Vec<FieldElement>, TokenIdCalldata), StarknetClientError>
+where
+    C: StarknetClient + Sync + ?Sized,
+    F: Fn(&[FieldElement]) -> Vec<FieldElement>,
+{
+    match call_contract_selectors(
+        client,
+        contract,
+        selectors,
+        calldata(&token_id.to_felts()),
+        block,
+    )
+    .await
+    {
+        Ok(felts) => return Ok((felts, TokenIdCalldata::U256)),
+        Err(StarknetClientError::InputTooLong) => (),
+        Err(e) => return Err(e),
+    };
+
+    let token_id_felt = FieldElement::try_from(token_id)
+        .map_err(|e| StarknetClientError::Conversion(e.to_string()))?;
+
+    let felts = call_contract_selectors(
+        client,
+        contract,
+        selectors,
+        calldata(&[token_id_felt]),
+        block,
+    )
+    .await?;
+
+    Ok((felts, TokenIdCalldata::Felt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::starknet_utils::byte_array::ByteArray;
+    use crate::starknet_utils::client::MockStarknetClient;
+    use starknet::core::types::BlockTag;
+    use starknet::core::utils::cairo_short_string_to_felt;
+    use starknet::macros::felt;
+
+    const COLLECTION: FieldElement = felt!("0xc011");
+    const BLOCK: BlockId = BlockId::Tag(BlockTag::Latest);
+
+    /// The entrypoint, the maximum number of calldata felts it accepts and its
+    /// result, an error being a revert reason.
+    type Entrypoint = (FieldElement, usize, Result<Vec<FieldElement>, String>);
+
+    /// A contract answering each of the given entrypoints, and rejecting
+    /// longer calldata like Cairo 0 does.
+    fn mock(entrypoints: Vec<Entrypoint>) -> MockStarknetClient {
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(move |contract, selector, calldata, _| {
+                assert_eq!(contract, COLLECTION);
+                match entrypoints.iter().find(|(s, _, _)| *s == selector) {
+                    None => Err(StarknetClientError::EntrypointNotFound("".to_string())),
+                    Some((_, len, _)) if calldata.len() > *len => {
+                        Err(StarknetClientError::InputTooLong)
+                    }
+                    Some((_, _, result)) => result.clone().map_err(StarknetClientError::Contract),
+                }
+            });
+        client
+    }
+
+    fn short_string(s: &str) -> FieldElement {
+        cairo_short_string_to_felt(s).unwrap()
+    }
+
+    fn token_id() -> CairoU256 {
+        CairoU256::from(42_u64)
+    }
+
+    #[tokio::test]
+    async fn cairo1_byte_array_token_uri() {
+        let uri = "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/42.json";
+        let client = mock(vec![(
+            selector!("token_uri"),
+            2,
+            Ok(ByteArray::from_string(uri).to_felts()),
+        )]);
+
+        let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_uri,
+            TokenUri {
+                uri: uri.to_string(),
+                encoding: TokenUriEncoding::ByteArray,
+                token_id_calldata: Some(TokenIdCalldata::U256),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn camel_case_and_erc1155_entrypoints() {
+        let legacy_long_string = vec![
+            felt!("0x2"),
+            short_string("https://"),
+            short_string("a.io/42"),
+        ];
+
+        for entrypoint in [
+            selector!("tokenURI"),
+            selector!("tokenUri"),
+            selector!("uri"),
+        ] {
+            let client = mock(vec![(entrypoint, 2, Ok(legacy_long_string.clone()))]);
+
+            let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+                .await
+                .unwrap();
+
+            assert_eq!(token_uri.uri, "https://a.io/42");
+            assert_eq!(token_uri.encoding, TokenUriEncoding::LegacyLongString);
+        }
+    }
+
+    #[tokio::test]
+    async fn cairo0_felt_token_id() {
+        let client = mock(vec![(
+            selector!("tokenURI"),
+            1,
+            Ok(vec![short_string("ipfs://bafy/42")]),
+        )]);
+
+        let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_uri,
+            TokenUri {
+                uri: "ipfs://bafy/42".to_string(),
+                encoding: TokenUriEncoding::ShortString,
+                token_id_calldata: Some(TokenIdCalldata::Felt),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn base_uri_fallback() {
+        let base_uri = Ok(vec![short_string("https://a.io/")]);
+        let expected = TokenUri {
+            uri: "https://a.io/42".to_string(),
+            encoding: TokenUriEncoding::BaseUriWithTokenId,
+            token_id_calldata: None,
+        };
+
+        // Without token URI entrypoint.
+        let client = mock(vec![(selector!("baseURI"), 0, base_uri.clone())]);
+        let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+            .await
+            .unwrap();
+        assert_eq!(token_uri, expected);
+
+        // With an empty token URI.
+        let client = mock(vec![
+            (selector!("token_uri"), 2, Ok(vec![FieldElement::ZERO])),
+            (selector!("base_uri"), 0, base_uri),
+        ]);
+        let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+            .await
+            .unwrap();
+        assert_eq!(token_uri, expected);
+    }
+
+    #[tokio::test]
+    async fn empty_token_uri_without_base_uri() {
+        let client = mock(vec![(
+            selector!("token_uri"),
+            2,
+            Ok(vec![FieldElement::ZERO]),
+        )]);
+
+        let token_uri = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_uri,
+            TokenUri {
+                uri: "".to_string(),
+                encoding: TokenUriEncoding::ShortString,
+                token_id_calldata: Some(TokenIdCalldata::U256),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn token_uri_errors() {
+        // No URI entrypoint at all.
+        let result = fetch_token_uri(&mock(vec![]), COLLECTION, token_id(), BLOCK).await;
+        assert!(matches!(
+            result,
+            Err(TokenUriError::Client(
+                StarknetClientError::EntrypointNotFound(_)
+            ))
+        ));
+
+        // A revert is not a missing entrypoint, the base URI is not used.
+        let client = mock(vec![
+            (
+                selector!("token_uri"),
+                2,
+                Err("ERC721: invalid token ID".to_string()),
+            ),
+            (
+                selector!("base_uri"),
+                0,
+                Ok(vec![short_string("https://a.io/")]),
+            ),
+        ]);
+        let result = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK).await;
+        assert!(matches!(
+            result,
+            Err(TokenUriError::Client(StarknetClientError::Contract(_)))
+        ));
+
+        let client = mock(vec![(selector!("token_uri"), 2, Ok(vec![]))]);
+        let result = fetch_token_uri(&client, COLLECTION, token_id(), BLOCK).await;
+        assert!(matches!(
+            result,
+            Err(TokenUriError::Parse(ParseError::NoValueFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn call_with_token_id_builds_the_calldata() {
+        let owner = felt!("0xa11ce");
+        let mut client = MockStarknetClient::default();
+        client
+            .expect_call_contract()
+            .returning(move |_, _, calldata, _| match calldata[..] {
+                [account, id] if account == owner && id == felt!("0x2a") => Ok(vec![felt!("0x1")]),
+                _ => Err(StarknetClientError::InputTooLong),
+            });
+
+        let (felts, token_id_calldata) = call_with_token_id(
+            &client,
+            COLLECTION,
+            &[selector!("balance_of")],
+            token_id(),
+            BLOCK,
+            |id| [&[owner], id].concat(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(felts, vec![felt!("0x1")]);
+        assert_eq!(token_id_calldata, TokenIdCalldata::Felt);
+    }
+
+    #[tokio::test]
+    async fn token_id_larger_than_a_felt() {
+        let client = mock(vec![(selector!("token_uri"), 1, Ok(vec![]))]);
+        let token_id = CairoU256::from_felts(
+            FieldElement::ZERO,
+            felt!("0x80000000000000000000000000000000"),
+        )
+        .unwrap();
+
+        let result =
+            call_with_token_id(&client, COLLECTION, &URI_SELECTORS, token_id, BLOCK, |id| {
+                id.to_vec()
+            })
+            .await;
+
+        assert!(matches!(result, Err(StarknetClientError::Conversion(_))));
+    }
+}
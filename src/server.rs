@@ -0,0 +1,83 @@
+//! Local HTTP server exposing the metrics and the health of the indexer.
+use crate::health::Readiness;
+use crate::metrics::Metrics;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::info;
+
+/// Content type of the Prometheus text format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("HTTP server error: {0}")]
+    Hyper(#[from] hyper::Error),
+}
+
+/// Serves `GET /metrics` if `metrics` is given, and `GET /healthz` and
+/// `GET /readyz` if `readiness` is given, on the given address until
+/// the process ends.
+pub async fn serve(
+    address: SocketAddr,
+    metrics: Option<Arc<Metrics>>,
+    readiness: Option<Arc<Readiness>>,
+) -> Result<(), ServerError> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        let readiness = readiness.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let metrics = metrics.clone();
+                let readiness = readiness.clone();
+                async move {
+                    Ok::<_, Infallible>(route(&request, metrics.as_deref(), readiness.as_deref()))
+                }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)?.serve(make_service);
+    info!(address = %server.local_addr(), "HTTP server listening");
+
+    Ok(server.await?)
+}
+
+fn route(
+    request: &Request<Body>,
+    metrics: Option<&Metrics>,
+    readiness: Option<&Readiness>,
+) -> Response<Body> {
+    match (request.method(), request.uri().path(), metrics, readiness) {
+        (&Method::GET, "/metrics", Some(metrics), _) => {
+            response(StatusCode::OK, METRICS_CONTENT_TYPE, metrics.render())
+        }
+        // The process is alive as long as it answers.
+        (&Method::GET, "/healthz", _, Some(_)) => {
+            response(StatusCode::OK, "text/plain", "ok".to_string())
+        }
+        (&Method::GET, "/readyz", _, Some(readiness)) => {
+            let report = readiness.check();
+            let status = if report.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let body = serde_json::to_string(&report).unwrap_or_default();
+            response(status, "application/json", body)
+        }
+        _ => response(StatusCode::NOT_FOUND, "text/plain", "Not found".to_string()),
+    }
+}
+
+fn response(status: StatusCode, content_type: &str, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, value);
+    }
+    response
+}
@@ -10,11 +10,30 @@
 //! In the data structure, everything is represented as a felt to be compatible
 //! with the Cairo implementation.
 
+use std::ops::{Add, AddAssign};
 use std::string::FromUtf8Error;
 
 use starknet::core::types::FieldElement;
 
 const MAX_WORD_LEN: usize = 31;
+const MAX_PENDING_WORD_LEN: usize = MAX_WORD_LEN - 1;
+
+/// Errors that can occur while deserializing a `ByteArray` from felts.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+pub enum ByteArrayError {
+    #[error("Not enough felts to deserialize a ByteArray")]
+    NotEnoughFelts,
+    #[error("Invalid length: {0}")]
+    InvalidLength(String),
+    #[error("Data word at index {0} is not smaller than 2^248")]
+    DataWordTooLarge(usize),
+    #[error("Pending word length {0} is greater than 30")]
+    PendingWordLenTooLarge(usize),
+    #[error("Pending word does not fit in {0} bytes")]
+    PendingWordTooLarge(usize),
+    #[error("Word does not fit in {0} bytes")]
+    WordTooLarge(usize),
+}
 
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 pub struct ByteArray {
@@ -32,11 +51,20 @@ impl ByteArray {
     ///
     /// * `string` - The always valid UTF-8 string to convert.
     pub fn from_string(string: &str) -> Self {
-        let bytes = string.as_bytes();
+        Self::from_bytes(string.as_bytes())
+    }
+
+    /// Converts raw bytes into a `ByteArray`, without any assumption
+    /// on the encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes to pack into words of 31 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
         let chunks: Vec<_> = bytes.chunks(MAX_WORD_LEN).collect();
 
-        let remainder = if bytes.len() % MAX_WORD_LEN != 0 {
-            chunks.last().copied().map(|last| last.to_vec())
+        let remainder = if !bytes.len().is_multiple_of(MAX_WORD_LEN) {
+            chunks.last().copied()
         } else {
             None
         };
@@ -48,17 +76,16 @@ impl ByteArray {
         };
 
         let (pending_word, pending_word_len) = if let Some(r) = remainder {
-            let len = r.len();
             (
                 // Safe to unwrap as pending word always fit in a felt.
-                FieldElement::from_byte_slice_be(&r).unwrap(),
-                len,
+                FieldElement::from_byte_slice_be(r).unwrap(),
+                r.len(),
             )
         } else {
             (FieldElement::ZERO, 0)
         };
 
-        let mut data = Vec::new();
+        let mut data = Vec::with_capacity(full_chunks.len());
         for chunk in full_chunks {
             // Safe to unwrap as full chunks are 31 bytes long, always fit in a felt.
             data.push(FieldElement::from_byte_slice_be(chunk).unwrap())
@@ -71,46 +98,294 @@ impl ByteArray {
         }
     }
 
+    /// Returns the raw bytes contained in the `ByteArray`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bytes().collect()
+    }
+
+    /// Returns an iterator over the bytes of the `ByteArray`.
+    /// Words are unpacked one at a time, no allocation is done.
+    pub fn bytes(&self) -> Bytes<'_> {
+        Bytes {
+            byte_array: self,
+            next_word: 0,
+            buffer: [0; 32],
+            position: 32,
+        }
+    }
+
     /// Converts `ByteArray` instance into a UTF-8 encoded string on success.
     /// Returns error if the `ByteArray` contains an invalid UTF-8 string.
     pub fn to_string(&self) -> Result<String, FromUtf8Error> {
-        let mut s = String::new();
+        String::from_utf8(self.to_bytes())
+    }
+
+    /// Converts `ByteArray` instance into a UTF-8 encoded string,
+    /// replacing invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.to_bytes()).into_owned()
+    }
+
+    /// Returns the number of bytes in the `ByteArray`.
+    pub fn len(&self) -> usize {
+        self.data.len() * MAX_WORD_LEN + self.pending_word_len
+    }
+
+    /// Returns true if the `ByteArray` contains no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the byte at the given index, or `None` if the
+    /// index is out of bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the byte, starting from the first byte of `data`.
+    pub fn at(&self, index: usize) -> Option<u8> {
+        let word_index = index / MAX_WORD_LEN;
+        let index_in_word = index % MAX_WORD_LEN;
+
+        if word_index < self.data.len() {
+            // Full words always have their first byte equal to 0.
+            Some(self.data[word_index].to_bytes_be()[1 + index_in_word])
+        } else if word_index == self.data.len() && index_in_word < self.pending_word_len {
+            Some(self.pending_word.to_bytes_be()[32 - self.pending_word_len + index_in_word])
+        } else {
+            None
+        }
+    }
+
+    /// Appends a single byte at the end of the `ByteArray`.
+    pub fn append_byte(&mut self, byte: u8) {
+        self.append_bytes(&[byte]);
+    }
+
+    /// Appends a word of `len` bytes at the end of the `ByteArray`.
+    /// As in Cairo, the word must fit in `len` bytes, and `len` must be
+    /// at most 31.
+    ///
+    /// # Arguments
+    ///
+    /// * `word` - The word to append, right aligned in the felt.
+    /// * `len` - The number of bytes of the word to append.
+    pub fn append_word(&mut self, word: FieldElement, len: usize) -> Result<(), ByteArrayError> {
+        if len > MAX_WORD_LEN {
+            return Err(ByteArrayError::InvalidLength(len.to_string()));
+        }
+
+        if !fits_in_bytes(&word, len) {
+            return Err(ByteArrayError::WordTooLarge(len));
+        }
+
+        self.append_bytes(&word.to_bytes_be()[32 - len..]);
 
-        for d in &self.data {
-            // Chunks are always 31 bytes long (MAX_WORD_LEN).
-            s.push_str(&felt_to_utf8(d, MAX_WORD_LEN)?);
+        Ok(())
+    }
+
+    /// Appends the content of an other `ByteArray` at the end of this one.
+    pub fn append(&mut self, other: &ByteArray) {
+        if self.pending_word_len == 0 {
+            // Words are already aligned, no need to repack them.
+            self.data.extend(other.data.iter().copied());
+            self.pending_word = other.pending_word;
+            self.pending_word_len = other.pending_word_len;
+        } else {
+            self.append_bytes(&other.to_bytes());
+        }
+    }
+
+    /// Returns a new `ByteArray` with the content of `left` followed
+    /// by the content of `right`.
+    pub fn concat(left: &ByteArray, right: &ByteArray) -> Self {
+        let mut result = left.clone();
+        result.append(right);
+        result
+    }
+
+    /// Returns a new `ByteArray` with the bytes in reverse order.
+    pub fn rev(&self) -> Self {
+        let mut bytes = self.to_bytes();
+        bytes.reverse();
+        Self::from_bytes(&bytes)
+    }
+
+    /// Appends raw bytes, filling the pending word and pushing
+    /// every completed word into `data`.
+    fn append_bytes(&mut self, bytes: &[u8]) {
+        let mut word = [0_u8; MAX_WORD_LEN];
+        let mut word_len = self.pending_word_len;
+
+        word[..word_len].copy_from_slice(&self.pending_word.to_bytes_be()[32 - word_len..]);
+
+        for byte in bytes {
+            word[word_len] = *byte;
+            word_len += 1;
+
+            if word_len == MAX_WORD_LEN {
+                // Safe to unwrap as full words are 31 bytes long, always fit in a felt.
+                self.data
+                    .push(FieldElement::from_byte_slice_be(&word).unwrap());
+                word_len = 0;
+            }
+        }
+
+        // Safe to unwrap as pending word always fit in a felt.
+        self.pending_word = FieldElement::from_byte_slice_be(&word[..word_len]).unwrap();
+        self.pending_word_len = word_len;
+    }
+
+    /// Serializes the `ByteArray` the same way Cairo does:
+    /// `[data_len, data..., pending_word, pending_word_len]`.
+    pub fn to_felts(&self) -> Vec<FieldElement> {
+        let mut felts = Vec::with_capacity(self.data.len() + 3);
+
+        felts.push(FieldElement::from(self.data.len()));
+        felts.extend(self.data.iter().copied());
+        felts.push(self.pending_word);
+        felts.push(FieldElement::from(self.pending_word_len));
+
+        felts
+    }
+
+    /// Deserializes a `ByteArray` from the Cairo serialized form
+    /// `[data_len, data..., pending_word, pending_word_len]`.
+    ///
+    /// Returns the `ByteArray` along with the number of felts consumed,
+    /// as the `ByteArray` may be followed by other values in `felts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `felts` - The felts to read from, starting with `data_len`.
+    pub fn from_felts(felts: &[FieldElement]) -> Result<(Self, usize), ByteArrayError> {
+        let data_len = felts.first().ok_or(ByteArrayError::NotEnoughFelts)?;
+        let data_len = felt_to_usize(data_len)?;
+
+        // data_len + data + pending_word + pending_word_len.
+        let consumed = data_len
+            .checked_add(3)
+            .ok_or_else(|| ByteArrayError::InvalidLength(data_len.to_string()))?;
+
+        if felts.len() < consumed {
+            return Err(ByteArrayError::NotEnoughFelts);
         }
 
-        if self.pending_word_len > 0 {
-            s.push_str(&felt_to_utf8(&self.pending_word, self.pending_word_len)?);
+        let data = felts[1..1 + data_len].to_vec();
+        for (i, word) in data.iter().enumerate() {
+            if !fits_in_bytes(word, MAX_WORD_LEN) {
+                return Err(ByteArrayError::DataWordTooLarge(i));
+            }
         }
 
-        Ok(s)
+        let pending_word = felts[1 + data_len];
+        let pending_word_len = felt_to_usize(&felts[2 + data_len])?;
+
+        if pending_word_len > MAX_PENDING_WORD_LEN {
+            return Err(ByteArrayError::PendingWordLenTooLarge(pending_word_len));
+        }
+
+        if !fits_in_bytes(&pending_word, pending_word_len) {
+            return Err(ByteArrayError::PendingWordTooLarge(pending_word_len));
+        }
+
+        Ok((
+            Self {
+                data,
+                pending_word,
+                pending_word_len,
+            },
+            consumed,
+        ))
     }
 }
 
-/// Converts a felt into a UTF-8 string.
-/// Returns an error if the felt contains an invalid UTF-8 string.
-///
-/// # Arguments
-///
-/// * `felt` - The `FieldElement` to convert. In the context of `ByteArray` this
-///            felt always contains at most 31 bytes.
-/// * `len` - The number of bytes in the felt, at most 31. In the context
-///           of `ByteArray`, we don't need to check `len` as the `MAX_WORD_LEN`
-///           already protect against that.
-fn felt_to_utf8(felt: &FieldElement, len: usize) -> Result<String, FromUtf8Error> {
-    let mut buffer = Vec::new();
+/// Returns true if the felt value is strictly lower than `2^(8 * len)`.
+fn fits_in_bytes(felt: &FieldElement, len: usize) -> bool {
+    felt.to_bytes_be()[..32 - len].iter().all(|b| *b == 0)
+}
+
+/// Converts a felt used as a length into a `usize`.
+fn felt_to_usize(felt: &FieldElement) -> Result<usize, ByteArrayError> {
+    u64::try_from(*felt)
+        .ok()
+        .and_then(|v| usize::try_from(v).ok())
+        .ok_or_else(|| ByteArrayError::InvalidLength(felt.to_string()))
+}
+
+/// Iterator over the bytes of a `ByteArray`, see [`ByteArray::bytes`].
+#[derive(Debug, Clone)]
+pub struct Bytes<'a> {
+    byte_array: &'a ByteArray,
+    /// Index of the next word to unpack, `data.len()` being the pending word.
+    next_word: usize,
+    /// Big endian bytes of the current word.
+    buffer: [u8; 32],
+    /// Position of the next byte to yield in `buffer`, 32 when exhausted.
+    position: usize,
+}
+
+impl Bytes<'_> {
+    /// Loads the next word into the buffer. Returns false if there
+    /// is no more word to unpack.
+    fn load_next_word(&mut self) -> bool {
+        let data_len = self.byte_array.data.len();
 
-    // ByteArray always enforce to have the first byte equal to 0.
-    // That's why we start to 1.
-    for byte in felt.to_bytes_be()[1 + MAX_WORD_LEN - len..].iter() {
-        buffer.push(*byte)
+        let (word, len) = if self.next_word < data_len {
+            (&self.byte_array.data[self.next_word], MAX_WORD_LEN)
+        } else if self.next_word == data_len {
+            (
+                &self.byte_array.pending_word,
+                self.byte_array.pending_word_len,
+            )
+        } else {
+            return false;
+        };
+
+        self.buffer = word.to_bytes_be();
+        // ByteArray words are right aligned in the felt.
+        self.position = 32_usize.saturating_sub(len);
+        self.next_word += 1;
+
+        true
     }
 
-    String::from_utf8(buffer)
+    /// Number of bytes not yet yielded.
+    fn remaining(&self) -> usize {
+        let data_len = self.byte_array.data.len();
+
+        let not_loaded = if self.next_word <= data_len {
+            (data_len - self.next_word) * MAX_WORD_LEN + self.byte_array.pending_word_len
+        } else {
+            0
+        };
+
+        (32 - self.position) + not_loaded
+    }
+}
+
+impl Iterator for Bytes<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.position >= 32 {
+            if !self.load_next_word() {
+                return None;
+            }
+        }
+
+        let byte = self.buffer[self.position];
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
 }
 
+impl ExactSizeIterator for Bytes<'_> {}
+
 impl From<String> for ByteArray {
     fn from(value: String) -> Self {
         ByteArray::from_string(&value)
@@ -122,3 +397,206 @@ impl From<&str> for ByteArray {
         ByteArray::from_string(value)
     }
 }
+
+impl From<&[u8]> for ByteArray {
+    fn from(value: &[u8]) -> Self {
+        ByteArray::from_bytes(value)
+    }
+}
+
+impl From<Vec<u8>> for ByteArray {
+    fn from(value: Vec<u8>) -> Self {
+        ByteArray::from_bytes(&value)
+    }
+}
+
+impl Add for ByteArray {
+    type Output = ByteArray;
+
+    fn add(mut self, rhs: ByteArray) -> ByteArray {
+        self.append(&rhs);
+        self
+    }
+}
+
+impl Add<&ByteArray> for ByteArray {
+    type Output = ByteArray;
+
+    fn add(mut self, rhs: &ByteArray) -> ByteArray {
+        self.append(rhs);
+        self
+    }
+}
+
+impl AddAssign<&ByteArray> for ByteArray {
+    fn add_assign(&mut self, rhs: &ByteArray) {
+        self.append(rhs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lengths covering several words, and both sides of each word boundary.
+    const MAX_LEN: usize = 3 * MAX_WORD_LEN + 2;
+
+    /// ASCII string of `len` bytes, with distinct bytes in each word.
+    fn string(len: usize) -> String {
+        (0..len).map(|i| (b'!' + (i % 90) as u8) as char).collect()
+    }
+
+    #[test]
+    fn felts_round_trip() {
+        for len in 0..=MAX_LEN {
+            let s = string(len);
+            let byte_array = ByteArray::from_string(&s);
+            assert_eq!(byte_array.data.len(), len / MAX_WORD_LEN, "len {}", len);
+            assert_eq!(
+                byte_array.pending_word_len,
+                len % MAX_WORD_LEN,
+                "len {}",
+                len
+            );
+            assert_eq!(byte_array.len(), len);
+
+            let mut felts = byte_array.to_felts();
+            assert_eq!(felts.len(), len / MAX_WORD_LEN + 3);
+            // The `ByteArray` may be followed by other values.
+            felts.push(FieldElement::ONE);
+
+            let (decoded, consumed) = ByteArray::from_felts(&felts).unwrap();
+            assert_eq!(consumed, felts.len() - 1, "len {}", len);
+            assert_eq!(decoded, byte_array);
+            assert_eq!(decoded.to_string().unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn multibyte_chars_across_words() {
+        for len in 0..=MAX_LEN / 2 {
+            // Two bytes per char, split between two words every 31 bytes.
+            let s = "é".repeat(len);
+            let byte_array = ByteArray::from_string(&s);
+
+            let (decoded, _) = ByteArray::from_felts(&byte_array.to_felts()).unwrap();
+            assert_eq!(decoded.to_string().unwrap(), s);
+            assert_eq!(decoded.to_bytes(), s.as_bytes());
+        }
+    }
+
+    #[test]
+    fn append_and_concat() {
+        for left_len in 0..=MAX_LEN {
+            for right_len in 0..=2 * MAX_WORD_LEN + 1 {
+                let (left, right) = (string(left_len), string(right_len).to_lowercase());
+                let expected = ByteArray::from_string(&(left.clone() + &right));
+
+                let (left, right) = (ByteArray::from(left.as_str()), ByteArray::from(right));
+                let concat = ByteArray::concat(&left, &right);
+                assert_eq!(concat, expected, "{} + {}", left_len, right_len);
+
+                let mut appended = left.clone();
+                appended.append(&right);
+                assert_eq!(appended, expected, "{} + {}", left_len, right_len);
+                assert_eq!(left + &right, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn append_byte_by_byte() {
+        let s = string(MAX_LEN);
+        let mut byte_array = ByteArray::default();
+
+        for (i, byte) in s.bytes().enumerate() {
+            byte_array.append_byte(byte);
+            assert_eq!(byte_array, ByteArray::from_string(&s[..=i]));
+        }
+    }
+
+    #[test]
+    fn append_word_boundaries() {
+        let mut byte_array = ByteArray::from_string(&string(30));
+        let word = FieldElement::from_byte_slice_be(string(MAX_WORD_LEN).as_bytes()).unwrap();
+
+        byte_array.append_word(word, MAX_WORD_LEN).unwrap();
+        assert_eq!(
+            byte_array.to_string().unwrap(),
+            string(30) + &string(MAX_WORD_LEN)
+        );
+
+        assert_eq!(
+            byte_array.append_word(word, MAX_WORD_LEN + 1),
+            Err(ByteArrayError::InvalidLength("32".to_string()))
+        );
+        assert_eq!(
+            byte_array.append_word(word, MAX_WORD_LEN - 1),
+            Err(ByteArrayError::WordTooLarge(30))
+        );
+    }
+
+    #[test]
+    fn rev_and_at() {
+        for len in 0..=MAX_LEN {
+            let s = string(len);
+            let byte_array = ByteArray::from_string(&s);
+
+            let reversed: String = s.chars().rev().collect();
+            assert_eq!(byte_array.rev(), ByteArray::from_string(&reversed));
+
+            for (i, byte) in s.bytes().enumerate() {
+                assert_eq!(byte_array.at(i), Some(byte), "len {} at {}", len, i);
+            }
+            assert_eq!(byte_array.at(len), None);
+            assert_eq!(byte_array.bytes().len(), len);
+        }
+    }
+
+    #[test]
+    fn from_felts_errors() {
+        assert_eq!(
+            ByteArray::from_felts(&[]),
+            Err(ByteArrayError::NotEnoughFelts)
+        );
+
+        // One data word announced, none given.
+        let felts = [FieldElement::ONE, FieldElement::ZERO, FieldElement::ZERO];
+        assert_eq!(
+            ByteArray::from_felts(&felts),
+            Err(ByteArrayError::NotEnoughFelts)
+        );
+
+        let too_large = FieldElement::from_byte_slice_be(&[1; 32]).unwrap();
+        let felts = [
+            FieldElement::ONE,
+            too_large,
+            FieldElement::ZERO,
+            FieldElement::ZERO,
+        ];
+        assert_eq!(
+            ByteArray::from_felts(&felts),
+            Err(ByteArrayError::DataWordTooLarge(0))
+        );
+
+        let felts = [
+            FieldElement::ZERO,
+            FieldElement::ZERO,
+            FieldElement::from(31_u8),
+        ];
+        assert_eq!(
+            ByteArray::from_felts(&felts),
+            Err(ByteArrayError::PendingWordLenTooLarge(31))
+        );
+
+        let felts = [
+            FieldElement::ZERO,
+            FieldElement::from(256_u16),
+            FieldElement::ONE,
+        ];
+        assert_eq!(
+            ByteArray::from_felts(&felts),
+            Err(ByteArrayError::PendingWordTooLarge(1))
+        );
+    }
+}
@@ -1,16 +1,28 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use starknet::core::{types::FieldElement, utils::parse_cairo_short_string};
 use std::ops::Add;
 
 use super::byte_array::ByteArray;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ParseError {
     NoValueFound,
     ShortStringError,
     ByteArrayError,
 }
 
+/// Encoding of a Cairo string, as detected by `parse_cairo_string_with_encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CairoStringEncoding {
+    /// A single felt, at most 31 characters.
+    ShortString,
+    /// Legacy `Array<felt252>`: `[len, short_strings...]`.
+    LegacyLongString,
+    /// `ByteArray`: `[data_len, data..., pending_word, pending_word_len]`.
+    ByteArray,
+}
+
 /// Parse a Cairo "long string" represented as a Vec of FieldElements into a Rust String.
 ///
 /// # Arguments
@@ -19,52 +31,57 @@ pub enum ParseError {
 /// # Returns
 /// * A `Result` which is either the parsed Rust string or an error.
 pub fn parse_cairo_string(field_elements: Vec<FieldElement>) -> Result<String, ParseError> {
+    parse_cairo_string_with_encoding(&field_elements).map(|(s, _)| s)
+}
+
+/// Parse a Cairo string represented as FieldElements into a Rust String,
+/// returning the encoding that was detected.
+///
+/// # Arguments
+/// * `field_elements`: The FieldElements representing the Cairo string, which
+///   can be a short string, a legacy long string or a `ByteArray`.
+pub fn parse_cairo_string_with_encoding(
+    field_elements: &[FieldElement],
+) -> Result<(String, CairoStringEncoding), ParseError> {
     match field_elements.len() {
         0 => Err(ParseError::NoValueFound),
         // If the long_string contains only one FieldElement, try to parse it using the short string parser.
-        1 => match field_elements.first() {
-            Some(first_string_field_element) => {
-                match parse_cairo_short_string(first_string_field_element) {
-                    Ok(value) => Ok(value),
-                    Err(_) => Err(ParseError::ShortStringError),
-                }
-            }
-            None => Err(ParseError::NoValueFound),
+        1 => match parse_cairo_short_string(&field_elements[0]) {
+            Ok(value) => Ok((value, CairoStringEncoding::ShortString)),
+            Err(_) => Err(ParseError::ShortStringError),
         },
         // If the long_string has more than one FieldElement, parse each FieldElement sequentially
         // and concatenate their results.
         len => {
-            let first_element = field_elements.first().unwrap();
+            let first_element = field_elements[0];
 
+            // The first element is not a length if it does not fit in a usize.
             let a_size = first_element
                 .add(FieldElement::ONE)
                 .to_string()
                 .parse::<usize>()
-                .unwrap();
+                .ok();
 
-            if len == a_size {
+            if Some(len) == a_size {
                 let results: Result<Vec<_>, _> = field_elements[1..]
                     .iter()
                     .map(parse_cairo_short_string)
                     .collect();
 
                 results
-                    .map(|strings| strings.concat())
+                    .map(|strings| (strings.concat(), CairoStringEncoding::LegacyLongString))
                     .map_err(|_| ParseError::ShortStringError)
             } else {
-                let data = field_elements[1..field_elements.len() - 2].to_vec();
-                let pending_word = field_elements[field_elements.len() - 2];
-                let pending_word_len = field_elements[field_elements.len() - 1];
-                let pending_word_len = pending_word_len.to_string().parse::<usize>().unwrap();
+                let (byte_array, consumed) = ByteArray::from_felts(field_elements)
+                    .map_err(|_| ParseError::ByteArrayError)?;
 
-                let byte_array = ByteArray {
-                    data,
-                    pending_word,
-                    pending_word_len,
-                };
+                if consumed != len {
+                    return Err(ParseError::ByteArrayError);
+                }
 
                 byte_array
                     .to_string()
+                    .map(|s| (s, CairoStringEncoding::ByteArray))
                     .map_err(|_| ParseError::ByteArrayError)
             }
         }
@@ -0,0 +1,744 @@
+//! Support for Cairo `u256`, represented as two `u128` limbs.
+//!
+//! Arithmetic operators follow the Cairo semantics: they panic on overflow,
+//! underflow and division by zero, as `u256` operations do in Cairo.
+//! Checked, wrapping, overflowing and saturating variants are provided to
+//! handle those cases without panicking.
+use num_bigint::BigUint;
+use num_traits::Num;
+use starknet::core::types::FieldElement;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{
+    Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Div, DivAssign,
+    Mul, MulAssign, Not, Rem, RemAssign, Shl, ShlAssign, Shr, ShrAssign, Sub, SubAssign,
+};
+use std::str::FromStr;
+
+use super::to_hex_str;
+
+/// Errors that can occur while converting a value into a `CairoU256`.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+pub enum CairoU256Error {
+    #[error("Value does not fit in a u256: {0}")]
+    Overflow(String),
+    #[error("Invalid u256 string: {0}")]
+    InvalidString(String),
+    #[error("Value does not fit in a felt: {0}")]
+    FeltOverflow(String),
+    #[error("Felt does not fit in a u128 limb: {0}")]
+    LimbOverflow(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct CairoU256 {
+    pub low: u128,
+    pub high: u128,
+}
+
+impl CairoU256 {
+    pub const ZERO: CairoU256 = CairoU256 { low: 0, high: 0 };
+    pub const ONE: CairoU256 = CairoU256 { low: 1, high: 0 };
+    pub const MAX: CairoU256 = CairoU256 {
+        low: u128::MAX,
+        high: u128::MAX,
+    };
+
+    pub fn new(low: u128, high: u128) -> Self {
+        Self { low, high }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.low == 0 && self.high == 0
+    }
+
+    pub fn to_biguint(&self) -> BigUint {
+        let low_bytes = self.low.to_be_bytes();
+        let high_bytes = self.high.to_be_bytes();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend(high_bytes);
+        bytes.extend(low_bytes);
+
+        BigUint::from_bytes_be(&bytes[..])
+    }
+
+    pub fn to_hex(&self) -> String {
+        let token_id_big_uint = self.to_biguint();
+        to_hex_str(&token_id_big_uint)
+    }
+
+    pub fn to_decimal(&self, padded: bool) -> String {
+        let token_id_big_uint = self.to_biguint();
+        let token_id_str: String = token_id_big_uint.to_str_radix(10);
+
+        if padded {
+            format!("{:0>width$}", token_id_str, width = 78)
+        } else {
+            token_id_str
+        }
+    }
+
+    /// Parses an hexadecimal string, with or without the `0x` prefix.
+    pub fn from_hex_be(value: &str) -> Result<Self, CairoU256Error> {
+        let hex = value.strip_prefix("0x").unwrap_or(value);
+        // `from_str_radix` also accepts a `+` sign and `_` separators.
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(CairoU256Error::InvalidString(value.to_string()));
+        }
+
+        let biguint = BigUint::from_str_radix(hex, 16)
+            .map_err(|_| CairoU256Error::InvalidString(value.to_string()))?;
+
+        Self::try_from(&biguint).map_err(|_| CairoU256Error::Overflow(value.to_string()))
+    }
+
+    /// Parses a decimal string.
+    pub fn from_dec_str(value: &str) -> Result<Self, CairoU256Error> {
+        // `from_str_radix` also accepts a `+` sign and `_` separators.
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(CairoU256Error::InvalidString(value.to_string()));
+        }
+
+        let biguint = BigUint::from_str_radix(value, 10)
+            .map_err(|_| CairoU256Error::InvalidString(value.to_string()))?;
+
+        Self::try_from(&biguint).map_err(|_| CairoU256Error::Overflow(value.to_string()))
+    }
+
+    /// Builds a `CairoU256` from the `[low, high]` felts it is serialized
+    /// into in calldata and events.
+    /// Returns an error if one of the felts does not fit in a `u128`.
+    pub fn from_felts(low: FieldElement, high: FieldElement) -> Result<Self, CairoU256Error> {
+        let to_u128 = |felt: FieldElement| {
+            u128::try_from(felt).map_err(|_| CairoU256Error::LimbOverflow(felt.to_string()))
+        };
+
+        Ok(Self {
+            low: to_u128(low)?,
+            high: to_u128(high)?,
+        })
+    }
+
+    /// Serializes the `CairoU256` into `[low, high]` felts, as expected
+    /// in calldata.
+    pub fn to_felts(&self) -> [FieldElement; 2] {
+        [FieldElement::from(self.low), FieldElement::from(self.high)]
+    }
+
+    /// Keeps the 256 least significant bits of the given `BigUint`.
+    fn from_biguint_truncated(value: &BigUint) -> Self {
+        let mut limbs = value.iter_u64_digits();
+        let mut next = || u128::from(limbs.next().unwrap_or(0));
+
+        let low = next() | (next() << 64);
+        let high = next() | (next() << 64);
+
+        Self { low, high }
+    }
+
+    /// Returns the 256 least significant bits of the result, and true if
+    /// the result did not fit in a `u256`.
+    fn overflowing_from_biguint(value: &BigUint) -> (Self, bool) {
+        (Self::from_biguint_truncated(value), value.bits() > 256)
+    }
+
+    pub fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+        let (low, carry) = self.low.overflowing_add(rhs.low);
+        let (high, overflow_high) = self.high.overflowing_add(rhs.high);
+        let (high, overflow_carry) = high.overflowing_add(carry as u128);
+
+        (Self { low, high }, overflow_high || overflow_carry)
+    }
+
+    pub fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
+        let (low, borrow) = self.low.overflowing_sub(rhs.low);
+        let (high, underflow_high) = self.high.overflowing_sub(rhs.high);
+        let (high, underflow_borrow) = high.overflowing_sub(borrow as u128);
+
+        (Self { low, high }, underflow_high || underflow_borrow)
+    }
+
+    pub fn overflowing_mul(self, rhs: Self) -> (Self, bool) {
+        Self::overflowing_from_biguint(&(self.to_biguint() * rhs.to_biguint()))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_add(rhs) {
+            (v, false) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_sub(rhs) {
+            (v, false) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_mul(rhs) {
+            (v, false) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.checked_div_rem(rhs).map(|(q, _)| q)
+    }
+
+    pub fn checked_rem(self, rhs: Self) -> Option<Self> {
+        self.checked_div_rem(rhs).map(|(_, r)| r)
+    }
+
+    /// Returns the quotient and the remainder of the division,
+    /// or `None` if `rhs` is zero.
+    pub fn checked_div_rem(self, rhs: Self) -> Option<(Self, Self)> {
+        if rhs.is_zero() {
+            return None;
+        }
+
+        let (lhs, rhs) = (self.to_biguint(), rhs.to_biguint());
+
+        Some((
+            Self::from_biguint_truncated(&(&lhs / &rhs)),
+            Self::from_biguint_truncated(&(&lhs % &rhs)),
+        ))
+    }
+
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        self.overflowing_mul(rhs).0
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        self.checked_add(rhs).unwrap_or(Self::MAX)
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).unwrap_or(Self::ZERO)
+    }
+
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        self.checked_mul(rhs).unwrap_or(Self::MAX)
+    }
+}
+
+impl Ord for CairoU256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.high
+            .cmp(&other.high)
+            .then_with(|| self.low.cmp(&other.low))
+    }
+}
+
+impl PartialOrd for CairoU256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for CairoU256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_biguint())
+    }
+}
+
+impl fmt::LowerHex for CairoU256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.to_biguint(), f)
+    }
+}
+
+/// Arithmetic operators, panicking with the same messages as Cairo.
+macro_rules! impl_arithmetic_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident, $checked:ident, $msg:literal) => {
+        impl $op for CairoU256 {
+            type Output = CairoU256;
+
+            fn $method(self, rhs: CairoU256) -> CairoU256 {
+                self.$checked(rhs).expect($msg)
+            }
+        }
+
+        impl $op_assign for CairoU256 {
+            fn $method_assign(&mut self, rhs: CairoU256) {
+                *self = $op::$method(*self, rhs);
+            }
+        }
+    };
+}
+
+impl_arithmetic_op!(
+    Add,
+    add,
+    AddAssign,
+    add_assign,
+    checked_add,
+    "u256_add Overflow"
+);
+impl_arithmetic_op!(
+    Sub,
+    sub,
+    SubAssign,
+    sub_assign,
+    checked_sub,
+    "u256_sub Overflow"
+);
+impl_arithmetic_op!(
+    Mul,
+    mul,
+    MulAssign,
+    mul_assign,
+    checked_mul,
+    "u256_mul Overflow"
+);
+impl_arithmetic_op!(
+    Div,
+    div,
+    DivAssign,
+    div_assign,
+    checked_div,
+    "Division by 0"
+);
+impl_arithmetic_op!(
+    Rem,
+    rem,
+    RemAssign,
+    rem_assign,
+    checked_rem,
+    "Division by 0"
+);
+
+/// Bitwise operators, applied on both limbs.
+macro_rules! impl_bit_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident) => {
+        impl $op for CairoU256 {
+            type Output = CairoU256;
+
+            fn $method(self, rhs: CairoU256) -> CairoU256 {
+                CairoU256 {
+                    low: $op::$method(self.low, rhs.low),
+                    high: $op::$method(self.high, rhs.high),
+                }
+            }
+        }
+
+        impl $op_assign for CairoU256 {
+            fn $method_assign(&mut self, rhs: CairoU256) {
+                *self = $op::$method(*self, rhs);
+            }
+        }
+    };
+}
+
+impl_bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+impl_bit_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+impl_bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+
+impl Not for CairoU256 {
+    type Output = CairoU256;
+
+    fn not(self) -> CairoU256 {
+        CairoU256 {
+            low: !self.low,
+            high: !self.high,
+        }
+    }
+}
+
+/// Shifting by 256 bits or more results in zero.
+impl Shl<u32> for CairoU256 {
+    type Output = CairoU256;
+
+    fn shl(self, rhs: u32) -> CairoU256 {
+        match rhs {
+            0 => self,
+            1..=127 => CairoU256 {
+                low: self.low << rhs,
+                high: (self.high << rhs) | (self.low >> (128 - rhs)),
+            },
+            128..=255 => CairoU256 {
+                low: 0,
+                high: self.low << (rhs - 128),
+            },
+            _ => CairoU256::ZERO,
+        }
+    }
+}
+
+/// Shifting by 256 bits or more results in zero.
+impl Shr<u32> for CairoU256 {
+    type Output = CairoU256;
+
+    fn shr(self, rhs: u32) -> CairoU256 {
+        match rhs {
+            0 => self,
+            1..=127 => CairoU256 {
+                low: (self.low >> rhs) | (self.high << (128 - rhs)),
+                high: self.high >> rhs,
+            },
+            128..=255 => CairoU256 {
+                low: self.high >> (rhs - 128),
+                high: 0,
+            },
+            _ => CairoU256::ZERO,
+        }
+    }
+}
+
+impl ShlAssign<u32> for CairoU256 {
+    fn shl_assign(&mut self, rhs: u32) {
+        *self = *self << rhs;
+    }
+}
+
+impl ShrAssign<u32> for CairoU256 {
+    fn shr_assign(&mut self, rhs: u32) {
+        *self = *self >> rhs;
+    }
+}
+
+impl From<u128> for CairoU256 {
+    fn from(value: u128) -> Self {
+        Self {
+            low: value,
+            high: 0,
+        }
+    }
+}
+
+impl From<u64> for CairoU256 {
+    fn from(value: u64) -> Self {
+        u128::from(value).into()
+    }
+}
+
+impl From<FieldElement> for CairoU256 {
+    fn from(value: FieldElement) -> Self {
+        let bytes = value.to_bytes_be();
+        let (high, low) = bytes.split_at(16);
+
+        // Safe to unwrap as both halves are 16 bytes long.
+        Self {
+            low: u128::from_be_bytes(low.try_into().unwrap()),
+            high: u128::from_be_bytes(high.try_into().unwrap()),
+        }
+    }
+}
+
+impl TryFrom<CairoU256> for FieldElement {
+    type Error = CairoU256Error;
+
+    fn try_from(value: CairoU256) -> Result<Self, Self::Error> {
+        let mut bytes = [0_u8; 32];
+        bytes[..16].copy_from_slice(&value.high.to_be_bytes());
+        bytes[16..].copy_from_slice(&value.low.to_be_bytes());
+
+        FieldElement::from_bytes_be(&bytes)
+            .map_err(|_| CairoU256Error::FeltOverflow(value.to_hex()))
+    }
+}
+
+impl TryFrom<&BigUint> for CairoU256 {
+    type Error = CairoU256Error;
+
+    fn try_from(value: &BigUint) -> Result<Self, Self::Error> {
+        match Self::overflowing_from_biguint(value) {
+            (v, false) => Ok(v),
+            _ => Err(CairoU256Error::Overflow(value.to_string())),
+        }
+    }
+}
+
+impl TryFrom<BigUint> for CairoU256 {
+    type Error = CairoU256Error;
+
+    fn try_from(value: BigUint) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+/// Parses a decimal string, or an hexadecimal string if prefixed by `0x`.
+impl FromStr for CairoU256 {
+    type Err = CairoU256Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            Self::from_hex_be(s)
+        } else {
+            Self::from_dec_str(s)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const U128_MAX: CairoU256 = CairoU256 {
+        low: u128::MAX,
+        high: 0,
+    };
+
+    fn two_pow(bits: u32) -> BigUint {
+        BigUint::from(1_u8) << bits
+    }
+
+    #[test]
+    fn add_carries_into_high() {
+        assert_eq!(U128_MAX + CairoU256::ONE, CairoU256::new(0, 1));
+        assert_eq!(U128_MAX + U128_MAX, CairoU256::new(u128::MAX - 1, 1));
+        assert_eq!(
+            CairoU256::MAX.overflowing_add(CairoU256::ONE),
+            (CairoU256::ZERO, true)
+        );
+        assert_eq!(CairoU256::MAX.checked_add(CairoU256::ONE), None);
+        assert_eq!(
+            CairoU256::MAX.wrapping_add(CairoU256::from(2_u64)),
+            CairoU256::ONE
+        );
+        assert_eq!(
+            CairoU256::MAX.saturating_add(CairoU256::ONE),
+            CairoU256::MAX
+        );
+    }
+
+    #[test]
+    fn sub_borrows_from_high() {
+        assert_eq!(CairoU256::new(0, 1) - CairoU256::ONE, U128_MAX);
+        assert_eq!(
+            CairoU256::ZERO.overflowing_sub(CairoU256::ONE),
+            (CairoU256::MAX, true)
+        );
+        assert_eq!(CairoU256::ZERO.checked_sub(CairoU256::ONE), None);
+        assert_eq!(
+            CairoU256::ZERO.saturating_sub(CairoU256::ONE),
+            CairoU256::ZERO
+        );
+    }
+
+    #[test]
+    fn mul_overflow() {
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1 still fits.
+        assert_eq!(U128_MAX * U128_MAX, CairoU256::new(1, u128::MAX - 1));
+        let two_pow_128 = CairoU256::new(0, 1);
+        assert_eq!(two_pow_128.checked_mul(two_pow_128), None);
+        assert_eq!(
+            two_pow_128.overflowing_mul(two_pow_128),
+            (CairoU256::ZERO, true)
+        );
+        assert_eq!(two_pow_128.wrapping_mul(two_pow_128), CairoU256::ZERO);
+        assert_eq!(two_pow_128.saturating_mul(two_pow_128), CairoU256::MAX);
+        assert_eq!(
+            CairoU256::MAX.checked_mul(CairoU256::ONE),
+            Some(CairoU256::MAX)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "u256_add Overflow")]
+    fn add_panics_on_overflow() {
+        let _ = CairoU256::MAX + CairoU256::ONE;
+    }
+
+    #[test]
+    #[should_panic(expected = "u256_sub Overflow")]
+    fn sub_panics_on_underflow() {
+        let _ = CairoU256::ZERO - CairoU256::ONE;
+    }
+
+    #[test]
+    #[should_panic(expected = "Division by 0")]
+    fn div_panics_on_zero() {
+        let _ = CairoU256::ONE / CairoU256::ZERO;
+    }
+
+    #[test]
+    fn div_rem() {
+        assert_eq!(CairoU256::ONE.checked_div_rem(CairoU256::ZERO), None);
+        assert_eq!(CairoU256::ONE.checked_rem(CairoU256::ZERO), None);
+        assert_eq!(
+            CairoU256::MAX.checked_div_rem(CairoU256::new(0, 1)),
+            Some((U128_MAX, U128_MAX))
+        );
+        assert_eq!(CairoU256::MAX / CairoU256::MAX, CairoU256::ONE);
+        assert_eq!(
+            CairoU256::from(7_u64) % CairoU256::from(3_u64),
+            CairoU256::ONE
+        );
+    }
+
+    #[test]
+    fn shifts() {
+        let value = CairoU256::new(0x8000_0000_0000_0000_0000_0000_0000_0001, 1);
+
+        assert_eq!(value << 0, value);
+        assert_eq!(value >> 0, value);
+
+        assert_eq!(value << 1, CairoU256::new(2, 3));
+        assert_eq!(value >> 1, CairoU256::new(3 << 126, 0));
+
+        assert_eq!(value << 128, CairoU256::new(0, value.low));
+        assert_eq!(value >> 128, CairoU256::new(1, 0));
+
+        assert_eq!(CairoU256::ONE << 255, CairoU256::new(0, 1 << 127));
+        assert_eq!(CairoU256::MAX >> 255, CairoU256::ONE);
+
+        assert_eq!(CairoU256::MAX << 256, CairoU256::ZERO);
+        assert_eq!(CairoU256::MAX >> 256, CairoU256::ZERO);
+        assert_eq!(CairoU256::MAX << u32::MAX, CairoU256::ZERO);
+
+        let mut shifted = CairoU256::ONE;
+        shifted <<= 200;
+        shifted >>= 199;
+        assert_eq!(shifted, CairoU256::from(2_u64));
+    }
+
+    #[test]
+    fn bit_ops() {
+        let a = CairoU256::new(0b1100, 0b1010);
+        let b = CairoU256::new(0b1010, 0b0110);
+        assert_eq!(a & b, CairoU256::new(0b1000, 0b0010));
+        assert_eq!(a | b, CairoU256::new(0b1110, 0b1110));
+        assert_eq!(a ^ b, CairoU256::new(0b0110, 0b1100));
+        assert_eq!(!CairoU256::ZERO, CairoU256::MAX);
+    }
+
+    #[test]
+    fn ordering_compares_high_first() {
+        assert!(CairoU256::new(0, 1) > U128_MAX);
+        assert!(CairoU256::new(1, 1) > CairoU256::new(0, 1));
+        assert!(CairoU256::new(u128::MAX, 0) < CairoU256::new(0, 1));
+        assert_eq!(
+            CairoU256::new(5, 5).cmp(&CairoU256::new(5, 5)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn from_dec_str() {
+        assert_eq!(CairoU256::from_dec_str("0"), Ok(CairoU256::ZERO));
+        assert_eq!(
+            CairoU256::from_dec_str(&two_pow(128).to_string()),
+            Ok(CairoU256::new(0, 1))
+        );
+
+        let max = (two_pow(256) - 1_u8).to_string();
+        assert_eq!(CairoU256::from_dec_str(&max), Ok(CairoU256::MAX));
+        assert_eq!(
+            CairoU256::from_dec_str(&format!("000{}", max)),
+            Ok(CairoU256::MAX)
+        );
+        assert_eq!(CairoU256::MAX.to_decimal(false), max);
+        assert_eq!(CairoU256::ONE.to_decimal(true).len(), 78);
+
+        let too_big = two_pow(256).to_string();
+        assert_eq!(
+            CairoU256::from_dec_str(&too_big),
+            Err(CairoU256Error::Overflow(too_big))
+        );
+
+        for invalid in ["", "+1", "1_000", "-1", "0x10", "1 "] {
+            assert_eq!(
+                CairoU256::from_dec_str(invalid),
+                Err(CairoU256Error::InvalidString(invalid.to_string())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn from_hex_be() {
+        assert_eq!(CairoU256::from_hex_be("0x0"), Ok(CairoU256::ZERO));
+        assert_eq!(CairoU256::from_hex_be("ff"), Ok(CairoU256::from(255_u64)));
+        assert_eq!(
+            CairoU256::from_hex_be("0x100000000000000000000000000000000"),
+            Ok(CairoU256::new(0, 1))
+        );
+
+        let max = format!("0x{}", "f".repeat(64));
+        assert_eq!(CairoU256::from_hex_be(&max), Ok(CairoU256::MAX));
+        assert_eq!(CairoU256::MAX.to_hex(), max);
+
+        // Leading zeros don't count towards the 64 digits.
+        assert_eq!(
+            CairoU256::from_hex_be(&format!("0x{}1", "0".repeat(100))),
+            Ok(CairoU256::ONE)
+        );
+        assert_eq!(
+            CairoU256::from_hex_be(&format!("0x{}{}", "0".repeat(10), "f".repeat(64))),
+            Ok(CairoU256::MAX)
+        );
+
+        for too_big in [
+            format!("0x1{}", "0".repeat(64)),
+            "f".repeat(65),
+            "f".repeat(200),
+        ] {
+            assert_eq!(
+                CairoU256::from_hex_be(&too_big),
+                Err(CairoU256Error::Overflow(too_big.clone()))
+            );
+        }
+
+        for invalid in [
+            "", "0x", "0x+1", "+1", "0x1_0", "0xg", "0x 1", "0x-1", "0x0x1",
+        ] {
+            assert_eq!(
+                CairoU256::from_hex_be(invalid),
+                Err(CairoU256Error::InvalidString(invalid.to_string())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn from_str_picks_the_base() {
+        assert_eq!("0x10".parse(), Ok(CairoU256::from(16_u64)));
+        assert_eq!("10".parse(), Ok(CairoU256::from(10_u64)));
+        assert!("ff".parse::<CairoU256>().is_err());
+    }
+
+    #[test]
+    fn try_from_biguint() {
+        assert_eq!(CairoU256::try_from(two_pow(256) - 1_u8), Ok(CairoU256::MAX));
+        assert_eq!(CairoU256::try_from(&two_pow(128)), Ok(CairoU256::new(0, 1)));
+        assert!(CairoU256::try_from(two_pow(256)).is_err());
+        assert!(CairoU256::try_from(two_pow(300) + 1_u8).is_err());
+
+        let value = CairoU256::new(42, 7);
+        assert_eq!(CairoU256::try_from(value.to_biguint()), Ok(value));
+    }
+
+    #[test]
+    fn felts() {
+        let value = CairoU256::new(u128::MAX, 1);
+        let [low, high] = value.to_felts();
+        assert_eq!(CairoU256::from_felts(low, high), Ok(value));
+
+        let two_pow_128 = FieldElement::from(u128::MAX) + FieldElement::ONE;
+        assert_eq!(
+            CairoU256::from_felts(two_pow_128, FieldElement::ZERO),
+            Err(CairoU256Error::LimbOverflow(two_pow_128.to_string()))
+        );
+        assert!(CairoU256::from_felts(FieldElement::ZERO, two_pow_128).is_err());
+
+        assert_eq!(CairoU256::from(two_pow_128), CairoU256::new(0, 1));
+        assert_eq!(
+            FieldElement::try_from(CairoU256::new(0, 1)),
+            Ok(two_pow_128)
+        );
+        assert!(FieldElement::try_from(CairoU256::MAX).is_err());
+    }
+}
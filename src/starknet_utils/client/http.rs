@@ -1,7 +1,11 @@
 //! Starknet Client implementation using `JsonRpcHttp` provider.
+use crate::config::RetryConfig;
+use crate::metrics::Metrics;
 use crate::starknet_utils::EventResult;
 
-use super::{StarknetClient, StarknetClientError};
+use super::{
+    provider_error_variant, BlockHeader, DeployedContract, StarknetClient, StarknetClientError,
+};
 use async_trait::async_trait;
 use regex::Regex;
 use starknet::{
@@ -9,11 +13,17 @@ use starknet::{
     providers::{jsonrpc::HttpTransport, AnyProvider, JsonRpcClient, Provider, ProviderError},
 };
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, instrument, warn};
 use url::Url;
 
 const INPUT_TOO_SHORT: &str = "0x496e70757420746f6f2073686f727420666f7220617267756d656e7473";
 const INPUT_TOO_LONG: &str = "0x496e70757420746f6f206c6f6e6720666f7220617267756d656e7473";
-const FAILED_DESERIALIZE: &str = "0x4661696c656420746f20646573657269616c697a6520706172616d202331";
+/// "Failed to deserialize param #" without the param index, the missing
+/// param being any of the arguments.
+const FAILED_DESERIALIZE: &str = "0x4661696c656420746f20646573657269616c697a6520706172616d2023";
 const ENTRYPOINT_NOT_FOUND: &str = "not found in contract";
 
 #[derive(Debug)]
@@ -21,107 +31,241 @@ pub struct StarknetClientHttp {
     /// Provider is kept public to allow custom reuse of
     /// the raw provider elsewhere.
     pub provider: AnyProvider,
+    /// Maximum number of events per `starknet_getEvents` page.
+    pub events_chunk_size: u64,
+    pub retry: RetryConfig,
+    /// Origin of the RPC url, logged without the path which may contain an API key.
+    pub endpoint: String,
+    pub metrics: Option<Arc<Metrics>>,
+}
+
+impl StarknetClientHttp {
+    /// Creates a client with the given events page size and retry policy.
+    pub fn with_config(
+        rpc_url: &str,
+        events_chunk_size: u64,
+        retry: RetryConfig,
+    ) -> Result<Self, StarknetClientError> {
+        let mut client = Self::new(rpc_url)?;
+        client.events_chunk_size = events_chunk_size;
+        client.retry = retry;
+        Ok(client)
+    }
+
+    /// Records the requests in the given metrics.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Returns the given block, pending or not, with the hashes of its transactions.
+    #[instrument(
+        skip_all,
+        fields(
+            method = "block_with_tx_hashes",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
+    pub async fn block_with_tx_hashes(
+        &self,
+        block: BlockId,
+    ) -> Result<MaybePendingBlockWithTxHashes, StarknetClientError> {
+        self.traced("block_with_tx_hashes", async move {
+            self.retry(|| self.provider.get_block_with_tx_hashes(block))
+                .await
+                .map_err(StarknetClientError::Provider)
+        })
+        .await
+    }
+
+    /// Sends the request, and sends it again on transient errors
+    /// (rate limiting, transport errors) following the retry policy.
+    async fn retry<T, F, Fut>(&self, request: F) -> Result<T, ProviderError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match request().await {
+                Err(e @ ProviderError::RateLimited) | Err(e @ ProviderError::Other(_))
+                    if retry < self.retry.max_retries =>
+                {
+                    let backoff = self.retry.backoff(retry);
+                    retry += 1;
+                    warn!(
+                        retries = retry,
+                        error = provider_error_variant(&e),
+                        backoff_ms = backoff.as_millis() as u64,
+                        "Transient RPC error, retrying: {}",
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                r => {
+                    if retry > 0 {
+                        debug!(retries = retry, ok = r.is_ok(), "RPC request retried");
+                    }
+                    return r;
+                }
+            }
+        }
+    }
+
+    /// Awaits the body of a client method, logging its latency and error if any
+    /// in the span of the method, and recording them in the metrics.
+    async fn traced<T>(
+        &self,
+        method: &str,
+        body: impl Future<Output = Result<T, StarknetClientError>>,
+    ) -> Result<T, StarknetClientError> {
+        let start = Instant::now();
+        let result = body.await;
+        let latency = start.elapsed();
+        let latency_ms = latency.as_millis() as u64;
+
+        match &result {
+            Ok(_) => debug!(latency_ms, "Request done"),
+            // Contract errors are expected when probing interfaces.
+            Err(e) if e.is_contract_error() => {
+                debug!(latency_ms, error = e.variant(), "Request failed: {}", e)
+            }
+            Err(e) => warn!(latency_ms, error = e.variant(), "Request failed: {}", e),
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let error = result.as_ref().err().map(StarknetClientError::variant);
+            metrics.observe_rpc(method, latency, error);
+        }
+
+        result
+    }
+}
+
+/// Formats a block id for the logs, block hashes in hexadecimal.
+fn block_id_field(id: &BlockId) -> String {
+    match id {
+        BlockId::Number(n) => n.to_string(),
+        BlockId::Hash(h) => format!("{:#x}", h),
+        BlockId::Tag(BlockTag::Latest) => "latest".to_string(),
+        BlockId::Tag(BlockTag::Pending) => "pending".to_string(),
+    }
 }
 
 #[async_trait]
 impl StarknetClient for StarknetClientHttp {
-    ///
     fn new(rpc_url: &str) -> Result<StarknetClientHttp, StarknetClientError> {
         let rpc_url = Url::parse(rpc_url).map_err(|_| {
             StarknetClientError::Other("Can't parse RPC url to create the provider".to_string())
         })?;
 
+        let endpoint = rpc_url.origin().ascii_serialization();
         let provider = AnyProvider::JsonRpcHttp(JsonRpcClient::new(HttpTransport::new(rpc_url)));
 
-        Ok(Self { provider })
+        Ok(Self {
+            provider,
+            events_chunk_size: 1000,
+            retry: RetryConfig::default(),
+            endpoint,
+            metrics: None,
+        })
     }
 
     /// Transaction receipts don't have `EmittedEvent` but `Event` instead.
     /// This function aims at converting the `Event` into `EmittedEvent` to
     /// be compatible with all the indexing process.
+    #[instrument(
+        skip_all,
+        fields(
+            method = "events_from_tx_receipt",
+            transaction_hash = %format!("{:#x}", transaction_hash),
+            endpoint = %self.endpoint
+        )
+    )]
     async fn events_from_tx_receipt(
         &self,
         transaction_hash: FieldElement,
         keys: Option<Vec<Vec<FieldElement>>>,
     ) -> Result<Vec<EmittedEvent>, StarknetClientError> {
-        let receipt = self
-            .provider
-            .get_transaction_receipt(transaction_hash)
-            .await
-            .map_err(StarknetClientError::Provider)?;
-
-        let mut block_hash = FieldElement::MAX;
-        let mut block_number = u64::MAX;
-
-        let events = match receipt {
-            // We must assign the block hash and number for every type
-            // of transaction because we don't know in advance which
-            // type of txs are present in the block.
-            MaybePendingTransactionReceipt::Receipt(r) => match r {
-                TransactionReceipt::Invoke(inner) => {
-                    block_hash = inner.block_hash;
-                    block_number = inner.block_number;
-                    inner.events
-                }
-                TransactionReceipt::L1Handler(inner) => {
-                    block_hash = inner.block_hash;
-                    block_number = inner.block_number;
-                    inner.events
-                }
-                TransactionReceipt::Declare(inner) => {
-                    block_hash = inner.block_hash;
-                    block_number = inner.block_number;
-                    inner.events
-                }
-                TransactionReceipt::Deploy(inner) => {
-                    block_hash = inner.block_hash;
-                    block_number = inner.block_number;
-                    inner.events
-                }
-                TransactionReceipt::DeployAccount(inner) => {
-                    block_hash = inner.block_hash;
-                    block_number = inner.block_number;
-                    inner.events
+        self.traced("events_from_tx_receipt", async move {
+            let receipt = self
+                .retry(|| self.provider.get_transaction_receipt(transaction_hash))
+                .await
+                .map_err(StarknetClientError::Provider)?;
+
+            let mut block_hash = FieldElement::MAX;
+            let mut block_number = u64::MAX;
+
+            let events = match receipt {
+                // We must assign the block hash and number for every type
+                // of transaction because we don't know in advance which
+                // type of txs are present in the block.
+                MaybePendingTransactionReceipt::Receipt(r) => match r {
+                    TransactionReceipt::Invoke(inner) => {
+                        block_hash = inner.block_hash;
+                        block_number = inner.block_number;
+                        inner.events
+                    }
+                    TransactionReceipt::L1Handler(inner) => {
+                        block_hash = inner.block_hash;
+                        block_number = inner.block_number;
+                        inner.events
+                    }
+                    TransactionReceipt::Declare(inner) => {
+                        block_hash = inner.block_hash;
+                        block_number = inner.block_number;
+                        inner.events
+                    }
+                    TransactionReceipt::Deploy(inner) => {
+                        block_hash = inner.block_hash;
+                        block_number = inner.block_number;
+                        inner.events
+                    }
+                    TransactionReceipt::DeployAccount(inner) => {
+                        block_hash = inner.block_hash;
+                        block_number = inner.block_number;
+                        inner.events
+                    }
+                },
+                // For pending, we don't have the block hash or the block number.
+                // Default value of MAX is used.
+                MaybePendingTransactionReceipt::PendingReceipt(pr) => match pr {
+                    PendingTransactionReceipt::Invoke(inner) => inner.events,
+                    PendingTransactionReceipt::L1Handler(inner) => inner.events,
+                    PendingTransactionReceipt::Declare(inner) => inner.events,
+                    PendingTransactionReceipt::DeployAccount(inner) => inner.events,
+                },
+            };
+
+            let mut emitted_events = vec![];
+            for e in events {
+                // Without keys filter, all the events of the transaction are returned.
+                if keys
+                    .as_ref()
+                    .is_none_or(|keys| !e.keys.is_empty() && keys.contains(&e.keys))
+                {
+                    emitted_events.push(EmittedEvent {
+                        from_address: e.from_address,
+                        keys: e.keys,
+                        data: e.data,
+                        block_hash: Some(block_hash),
+                        block_number: Some(block_number),
+                        transaction_hash,
+                    })
                 }
-            },
-            // For pending, we don't have the block hash or the block number.
-            // Default value of MAX is used.
-            MaybePendingTransactionReceipt::PendingReceipt(pr) => match pr {
-                PendingTransactionReceipt::Invoke(inner) => inner.events,
-                PendingTransactionReceipt::L1Handler(inner) => inner.events,
-                PendingTransactionReceipt::Declare(inner) => inner.events,
-                PendingTransactionReceipt::DeployAccount(inner) => inner.events,
-            },
-        };
-
-        let mut emitted_events = vec![];
-        for e in events {
-            if keys.is_some()
-                && !e.keys.is_empty()
-                && keys.as_ref().map_or(false, |keys| keys.contains(&e.keys))
-            {
-                emitted_events.push(EmittedEvent {
-                    from_address: e.from_address,
-                    keys: e.keys,
-                    data: e.data,
-                    block_hash: Some(block_hash),
-                    block_number: Some(block_number),
-                    transaction_hash,
-                })
             }
-        }
 
-        Ok(emitted_events)
+            Ok(emitted_events)
+        })
+        .await
     }
 
-    ///
+    /// Only `latest` is resolved with a request, traced as `block_number`.
     async fn block_id_to_u64(&self, id: &BlockId) -> Result<u64, StarknetClientError> {
         match id {
-            BlockId::Tag(BlockTag::Latest) => Ok(self
-                .provider
-                .block_number()
-                .await
-                .map_err(StarknetClientError::Provider)?),
+            BlockId::Tag(BlockTag::Latest) => self.block_number().await,
             BlockId::Number(n) => Ok(*n),
             _ => Err(StarknetClientError::Conversion(
                 "BlockID can´t be converted to u64".to_string(),
@@ -129,7 +273,6 @@ impl StarknetClient for StarknetClientHttp {
         }
     }
 
-    ///
     fn parse_block_range(
         &self,
         from: &str,
@@ -141,7 +284,6 @@ impl StarknetClient for StarknetClientHttp {
         Ok((from_block, to_block))
     }
 
-    ///
     fn parse_block_id(&self, id: &str) -> Result<BlockId, StarknetClientError> {
         let regex_block_number = Regex::new("^[0-9]{1,}$").unwrap();
 
@@ -164,52 +306,248 @@ impl StarknetClient for StarknetClientHttp {
         }
     }
 
-    ///
+    #[instrument(
+        skip_all,
+        fields(
+            method = "deployed_contracts",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
+    async fn deployed_contracts(
+        &self,
+        block: BlockId,
+    ) -> Result<Vec<DeployedContract>, StarknetClientError> {
+        self.traced("deployed_contracts", async move {
+            let state_update = self
+                .retry(|| self.provider.get_state_update(block))
+                .await
+                .map_err(StarknetClientError::Provider)?;
+
+            let state_diff = match state_update {
+                MaybePendingStateUpdate::Update(update) => update.state_diff,
+                MaybePendingStateUpdate::PendingUpdate(update) => update.state_diff,
+            };
+
+            Ok(state_diff
+                .deployed_contracts
+                .into_iter()
+                .map(|c| DeployedContract {
+                    address: c.address,
+                    class_hash: c.class_hash,
+                    transaction_hash: None,
+                })
+                .collect())
+        })
+        .await
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            method = "deploy_transactions",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
+    async fn deploy_transactions(
+        &self,
+        block: BlockId,
+    ) -> Result<Vec<DeployedContract>, StarknetClientError> {
+        self.traced("deploy_transactions", async move {
+            let block = self
+                .retry(|| self.provider.get_block_with_txs(block))
+                .await
+                .map_err(StarknetClientError::Provider)?;
+
+            let transactions = match block {
+                MaybePendingBlockWithTxs::Block(block) => block.transactions,
+                MaybePendingBlockWithTxs::PendingBlock(block) => block.transactions,
+            };
+
+            let mut deployed = vec![];
+            for tx in transactions {
+                let (transaction_hash, class_hash) = match tx {
+                    Transaction::Deploy(tx) => (tx.transaction_hash, tx.class_hash),
+                    Transaction::DeployAccount(DeployAccountTransaction::V1(tx)) => {
+                        (tx.transaction_hash, tx.class_hash)
+                    }
+                    Transaction::DeployAccount(DeployAccountTransaction::V3(tx)) => {
+                        (tx.transaction_hash, tx.class_hash)
+                    }
+                    _ => continue,
+                };
+
+                let receipt = self
+                    .retry(|| self.provider.get_transaction_receipt(transaction_hash))
+                    .await
+                    .map_err(StarknetClientError::Provider)?;
+
+                let address = match receipt {
+                    MaybePendingTransactionReceipt::Receipt(TransactionReceipt::Deploy(r)) => {
+                        r.contract_address
+                    }
+                    MaybePendingTransactionReceipt::Receipt(TransactionReceipt::DeployAccount(
+                        r,
+                    )) => r.contract_address,
+                    MaybePendingTransactionReceipt::PendingReceipt(
+                        PendingTransactionReceipt::DeployAccount(r),
+                    ) => r.contract_address,
+                    _ => {
+                        return Err(StarknetClientError::Other(format!(
+                            "Unexpected receipt type for deploy transaction {:#x}",
+                            transaction_hash
+                        )))
+                    }
+                };
+
+                deployed.push(DeployedContract {
+                    address,
+                    class_hash,
+                    transaction_hash: Some(transaction_hash),
+                });
+            }
+
+            Ok(deployed)
+        })
+        .await
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            method = "class_hash_at",
+            block_id = %block_id_field(&block),
+            contract_address = %format!("{:#x}", contract_address),
+            endpoint = %self.endpoint
+        )
+    )]
+    async fn class_hash_at(
+        &self,
+        contract_address: FieldElement,
+        block: BlockId,
+    ) -> Result<FieldElement, StarknetClientError> {
+        self.traced("class_hash_at", async move {
+            self.retry(|| self.provider.get_class_hash_at(block, contract_address))
+                .await
+                .map_err(StarknetClientError::Provider)
+        })
+        .await
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            method = "block_time",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
     async fn block_time(&self, block: BlockId) -> Result<u64, StarknetClientError> {
-        let block = self
-            .provider
-            .get_block_with_tx_hashes(block)
-            .await
-            .map_err(StarknetClientError::Provider)?;
+        self.traced("block_time", async move {
+            let block = self
+                .retry(|| self.provider.get_block_with_tx_hashes(block))
+                .await
+                .map_err(StarknetClientError::Provider)?;
 
-        let timestamp = match block {
-            MaybePendingBlockWithTxHashes::Block(block) => block.timestamp,
-            MaybePendingBlockWithTxHashes::PendingBlock(block) => block.timestamp,
-        };
+            let timestamp = match block {
+                MaybePendingBlockWithTxHashes::Block(block) => block.timestamp,
+                MaybePendingBlockWithTxHashes::PendingBlock(block) => block.timestamp,
+            };
 
-        Ok(timestamp)
+            Ok(timestamp)
+        })
+        .await
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            method = "block_header",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
+    async fn block_header(&self, block: BlockId) -> Result<BlockHeader, StarknetClientError> {
+        self.traced("block_header", async move {
+            let block = self
+                .retry(|| self.provider.get_block_with_tx_hashes(block))
+                .await
+                .map_err(StarknetClientError::Provider)?;
+
+            match block {
+                MaybePendingBlockWithTxHashes::Block(block) => Ok(BlockHeader {
+                    number: block.block_number,
+                    hash: block.block_hash,
+                    parent_hash: block.parent_hash,
+                    timestamp: block.timestamp,
+                }),
+                MaybePendingBlockWithTxHashes::PendingBlock(_) => Err(StarknetClientError::Other(
+                    "Pending block has no header".to_string(),
+                )),
+            }
+        })
+        .await
     }
 
     /// Retuns the tx hashes of the asked block + the block timestamp.
+    #[instrument(
+        skip_all,
+        fields(
+            method = "block_txs_hashes",
+            block_id = %block_id_field(&block),
+            endpoint = %self.endpoint
+        )
+    )]
     async fn block_txs_hashes(
         &self,
         block: BlockId,
     ) -> Result<(u64, Vec<FieldElement>), StarknetClientError> {
-        let block = self
-            .provider
-            .get_block_with_tx_hashes(block)
-            .await
-            .map_err(StarknetClientError::Provider)?;
+        self.traced("block_txs_hashes", async move {
+            let block = self
+                .retry(|| self.provider.get_block_with_tx_hashes(block))
+                .await
+                .map_err(StarknetClientError::Provider)?;
 
-        let timestamp = match block {
-            MaybePendingBlockWithTxHashes::Block(block) => (block.timestamp, block.transactions),
-            MaybePendingBlockWithTxHashes::PendingBlock(block) => {
-                (block.timestamp, block.transactions)
-            }
-        };
+            let timestamp = match block {
+                MaybePendingBlockWithTxHashes::Block(block) => {
+                    (block.timestamp, block.transactions)
+                }
+                MaybePendingBlockWithTxHashes::PendingBlock(block) => {
+                    (block.timestamp, block.transactions)
+                }
+            };
 
-        Ok(timestamp)
+            Ok(timestamp)
+        })
+        .await
     }
 
-    ///
+    #[instrument(
+        skip_all,
+        fields(
+            method = "block_number",
+            endpoint = %self.endpoint
+        )
+    )]
     async fn block_number(&self) -> Result<u64, StarknetClientError> {
-        Ok(self
-            .provider
-            .block_number()
-            .await
-            .map_err(StarknetClientError::Provider)?)
+        self.traced("block_number", async move {
+            self.retry(|| self.provider.block_number())
+                .await
+                .map_err(StarknetClientError::Provider)
+        })
+        .await
     }
 
+    #[instrument(
+        skip_all,
+        fields(
+            method = "fetch_events",
+            from_block = %from_block.as_ref().map_or("none".to_string(), block_id_field),
+            to_block = %to_block.as_ref().map_or("none".to_string(), block_id_field),
+            endpoint = %self.endpoint
+        )
+    )]
     async fn fetch_events(
         &self,
         from_block: Option<BlockId>,
@@ -218,60 +556,23 @@ impl StarknetClient for StarknetClientHttp {
         contract_address: Option<FieldElement>,
         continuation_token: Option<String>,
     ) -> Result<EventResult, StarknetClientError> {
-        let mut events: HashMap<u64, Vec<EmittedEvent>> = HashMap::new();
-
-        let filter = EventFilter {
-            from_block,
-            to_block,
-            address: contract_address,
-            keys,
-        };
-
-        let chunk_size = 1000;
-
-        let event_page = self
-            .provider
-            .get_events(filter.clone(), continuation_token, chunk_size)
-            .await
-            .map_err(StarknetClientError::Provider)?;
-
-        event_page.events.iter().for_each(|e| {
-            if let Some(block_number) = e.block_number {
-                events
-                    .entry(block_number)
-                    .and_modify(|v| v.push(e.clone()))
-                    .or_insert(vec![e.clone()]);
-            }
-        });
-
-        Ok(EventResult {
-            events,
-            continuation_token: event_page.continuation_token,
-        })
-    }
-
-    ///
-    async fn fetch_all_block_events(
-        &self,
-        block_id: BlockId,
-        keys: Option<Vec<Vec<FieldElement>>>,
-    ) -> Result<HashMap<u64, Vec<EmittedEvent>>, StarknetClientError> {
-        let mut events: HashMap<u64, Vec<EmittedEvent>> = HashMap::new();
+        self.traced("fetch_events", async move {
+            let mut events: HashMap<u64, Vec<EmittedEvent>> = HashMap::new();
 
-        let filter = EventFilter {
-            from_block: Some(block_id),
-            to_block: Some(block_id),
-            address: None,
-            keys,
-        };
+            let filter = EventFilter {
+                from_block,
+                to_block,
+                address: contract_address,
+                keys,
+            };
 
-        let chunk_size = 1000;
-        let mut continuation_token: Option<String> = None;
+            let chunk_size = self.events_chunk_size;
 
-        loop {
             let event_page = self
-                .provider
-                .get_events(filter.clone(), continuation_token, chunk_size)
+                .retry(|| {
+                    self.provider
+                        .get_events(filter.clone(), continuation_token.clone(), chunk_size)
+                })
                 .await
                 .map_err(StarknetClientError::Provider)?;
 
@@ -280,21 +581,87 @@ impl StarknetClient for StarknetClientHttp {
                     events
                         .entry(block_number)
                         .and_modify(|v| v.push(e.clone()))
-                        .or_insert_with(|| vec![e.clone()]);
+                        .or_insert(vec![e.clone()]);
                 }
             });
 
-            continuation_token = event_page.continuation_token;
+            Ok(EventResult {
+                events,
+                continuation_token: event_page.continuation_token,
+            })
+        })
+        .await
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            method = "fetch_all_block_events",
+            block_id = %block_id_field(&block_id),
+            endpoint = %self.endpoint
+        )
+    )]
+    async fn fetch_all_block_events(
+        &self,
+        block_id: BlockId,
+        keys: Option<Vec<Vec<FieldElement>>>,
+    ) -> Result<HashMap<u64, Vec<EmittedEvent>>, StarknetClientError> {
+        self.traced("fetch_all_block_events", async move {
+            let mut events: HashMap<u64, Vec<EmittedEvent>> = HashMap::new();
+
+            let filter = EventFilter {
+                from_block: Some(block_id),
+                to_block: Some(block_id),
+                address: None,
+                keys,
+            };
+
+            let chunk_size = self.events_chunk_size;
+            let mut continuation_token: Option<String> = None;
+
+            loop {
+                let event_page = self
+                    .retry(|| {
+                        self.provider.get_events(
+                            filter.clone(),
+                            continuation_token.clone(),
+                            chunk_size,
+                        )
+                    })
+                    .await
+                    .map_err(StarknetClientError::Provider)?;
+
+                event_page.events.iter().for_each(|e| {
+                    if let Some(block_number) = e.block_number {
+                        events
+                            .entry(block_number)
+                            .and_modify(|v| v.push(e.clone()))
+                            .or_insert_with(|| vec![e.clone()]);
+                    }
+                });
+
+                continuation_token = event_page.continuation_token;
 
-            if continuation_token.is_none() {
-                break;
+                if continuation_token.is_none() {
+                    break;
+                }
             }
-        }
 
-        Ok(events)
+            Ok(events)
+        })
+        .await
     }
 
-    ///
+    #[instrument(
+        skip_all,
+        fields(
+            method = "call_contract",
+            block_id = %block_id_field(&block),
+            contract_address = %format!("{:#x}", contract_address),
+            selector = %format!("{:#x}", selector),
+            endpoint = %self.endpoint
+        )
+    )]
     async fn call_contract(
         &self,
         contract_address: FieldElement,
@@ -302,36 +669,154 @@ impl StarknetClient for StarknetClientHttp {
         calldata: Vec<FieldElement>,
         block: BlockId,
     ) -> Result<Vec<FieldElement>, StarknetClientError> {
-        let r = self
-            .provider
-            .call(
-                FunctionCall {
-                    contract_address,
-                    entry_point_selector: selector,
-                    calldata,
-                },
-                block,
-            )
-            .await;
-
-        match r {
-            Ok(felts) => Ok(felts),
-            Err(e) => {
-                if let ProviderError::StarknetError(StarknetError::ContractError(ref data)) = e {
-                    let s = data.revert_error.clone();
-                    if s.contains(ENTRYPOINT_NOT_FOUND) {
-                        Err(StarknetClientError::EntrypointNotFound(s))
-                    } else if s.contains(INPUT_TOO_SHORT) || s.contains(FAILED_DESERIALIZE) {
-                        Err(StarknetClientError::InputTooShort)
-                    } else if s.contains(INPUT_TOO_LONG) {
-                        Err(StarknetClientError::InputTooLong)
-                    } else {
-                        Err(StarknetClientError::Contract(s))
-                    }
-                } else {
-                    Err(StarknetClientError::Contract(e.to_string()))
-                }
-            }
+        self.traced("call_contract", async move {
+            let r = self
+                .retry(|| {
+                    self.provider.call(
+                        FunctionCall {
+                            contract_address,
+                            entry_point_selector: selector,
+                            calldata: calldata.clone(),
+                        },
+                        block,
+                    )
+                })
+                .await;
+
+            r.map_err(call_error)
+        })
+        .await
+    }
+}
+
+/// Maps the error of a contract call. Only the reverts of the contract are
+/// contract errors, the failures of the node or of the transport are kept as
+/// provider errors so they are not taken for a contract behavior.
+fn call_error(e: ProviderError) -> StarknetClientError {
+    let s = match e {
+        ProviderError::StarknetError(StarknetError::ContractError(data)) => data.revert_error,
+        e => return StarknetClientError::Provider(e),
+    };
+
+    if s.contains(ENTRYPOINT_NOT_FOUND) {
+        StarknetClientError::EntrypointNotFound(s)
+    } else if s.contains(INPUT_TOO_SHORT) || s.contains(FAILED_DESERIALIZE) {
+        StarknetClientError::InputTooShort
+    } else if s.contains(INPUT_TOO_LONG) {
+        StarknetClientError::InputTooLong
+    } else {
+        StarknetClientError::Contract(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::providers::jsonrpc::{HttpTransportError, JsonRpcClientError};
+
+    fn revert(reason: &str) -> ProviderError {
+        ProviderError::StarknetError(StarknetError::ContractError(ContractErrorData {
+            revert_error: reason.to_string(),
+        }))
+    }
+
+    #[test]
+    fn call_error_deserialize_any_param() {
+        // "Failed to deserialize param #1" and "... #2".
+        for reason in [
+            "0x4661696c656420746f20646573657269616c697a6520706172616d202331",
+            "0x4661696c656420746f20646573657269616c697a6520706172616d202332",
+        ] {
+            let e = call_error(revert(&format!("Execution failed: ({})", reason)));
+            assert!(matches!(e, StarknetClientError::InputTooShort), "{:?}", e);
         }
     }
+
+    #[test]
+    fn call_error_revert_kinds() {
+        assert!(matches!(
+            call_error(revert("Entry point 0x1 not found in contract.")),
+            StarknetClientError::EntrypointNotFound(_)
+        ));
+        assert!(matches!(
+            call_error(revert(INPUT_TOO_LONG)),
+            StarknetClientError::InputTooLong
+        ));
+        assert!(matches!(
+            call_error(revert("0x4552433732313a20696e76616c696420746f6b656e204944")),
+            StarknetClientError::Contract(_)
+        ));
+    }
+
+    #[test]
+    fn call_error_keeps_provider_failures() {
+        for e in [
+            ProviderError::RateLimited,
+            ProviderError::StarknetError(StarknetError::BlockNotFound),
+        ] {
+            let e = call_error(e);
+            assert!(matches!(e, StarknetClientError::Provider(_)), "{:?}", e);
+            assert!(!e.is_contract_error());
+        }
+    }
+
+    #[tokio::test]
+    async fn traced_labels_transport_failures() {
+        let metrics = Arc::new(Metrics::new());
+        let client = StarknetClientHttp::new("http://127.0.0.1:1")
+            .unwrap()
+            .with_metrics(metrics.clone());
+        let json = serde_json::from_str::<u64>("").unwrap_err();
+        let transport = JsonRpcClientError::TransportError(HttpTransportError::Json(json));
+        let error = StarknetClientError::Provider(ProviderError::Other(Box::new(transport)));
+
+        assert!(!error.is_contract_error());
+        let result: Result<(), _> = client.traced("block_number", async { Err(error) }).await;
+
+        assert!(result.is_err());
+        let errors = &metrics.rpc_errors;
+        assert_eq!(
+            errors
+                .with_label_values(&["block_number", "provider_transport"])
+                .get(),
+            1
+        );
+        assert_eq!(
+            errors
+                .with_label_values(&["block_number", "contract"])
+                .get(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn block_number_ids_are_not_requests() {
+        let metrics = Arc::new(Metrics::new());
+        let client = StarknetClientHttp::new("http://127.0.0.1:1")
+            .unwrap()
+            .with_metrics(metrics.clone());
+
+        assert_eq!(
+            client.block_id_to_u64(&BlockId::Number(5)).await.unwrap(),
+            5
+        );
+        assert!(client
+            .block_id_to_u64(&BlockId::Tag(BlockTag::Pending))
+            .await
+            .is_err());
+        assert_eq!(
+            metrics
+                .rpc_requests
+                .with_label_values(&["block_id_to_u64"])
+                .get(),
+            0
+        );
+        assert_eq!(
+            metrics
+                .rpc_requests
+                .with_label_values(&["block_number"])
+                .get(),
+            0
+        );
+    }
 }
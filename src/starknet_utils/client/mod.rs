@@ -3,6 +3,7 @@ use async_trait::async_trait;
 #[cfg(any(test, feature = "mock"))]
 use mockall::automock;
 use starknet::core::{types::FieldElement, types::*};
+use starknet::providers::jsonrpc::{HttpTransportError, JsonRpcClientError};
 use starknet::providers::ProviderError;
 use std::collections::HashMap;
 use std::marker::Sized;
@@ -26,46 +27,137 @@ pub enum StarknetClientError {
     Other(String),
 }
 
+impl StarknetClientError {
+    /// Name of the error variant, used as a low cardinality field in logs.
+    pub fn variant(&self) -> &'static str {
+        match self {
+            StarknetClientError::Contract(_) => "contract",
+            StarknetClientError::EntrypointNotFound(_) => "entrypoint_not_found",
+            StarknetClientError::InputTooLong => "input_too_long",
+            StarknetClientError::InputTooShort => "input_too_short",
+            StarknetClientError::Conversion(_) => "conversion",
+            StarknetClientError::Provider(e) => provider_error_variant(e),
+            StarknetClientError::Other(_) => "other",
+        }
+    }
+
+    /// Contract errors are expected while probing the interfaces of a contract,
+    /// unlike the errors of the node itself.
+    pub fn is_contract_error(&self) -> bool {
+        matches!(
+            self,
+            StarknetClientError::Contract(_)
+                | StarknetClientError::EntrypointNotFound(_)
+                | StarknetClientError::InputTooLong
+                | StarknetClientError::InputTooShort
+        )
+    }
+}
+
+/// Name of the provider error variant, used as a low cardinality field in logs.
+pub fn provider_error_variant(error: &ProviderError) -> &'static str {
+    match error {
+        ProviderError::StarknetError(_) => "provider_starknet",
+        ProviderError::RateLimited => "provider_rate_limited",
+        ProviderError::ArrayLengthMismatch => "provider_array_length_mismatch",
+        ProviderError::Other(e) if is_transport_error(e.as_any()) => "provider_transport",
+        ProviderError::Other(_) => "provider_other",
+    }
+}
+
+/// Failures to reach the node or to read its response, as opposed to the
+/// errors returned by the node.
+fn is_transport_error(error: &dyn std::any::Any) -> bool {
+    matches!(
+        error.downcast_ref::<JsonRpcClientError<HttpTransportError>>(),
+        Some(JsonRpcClientError::TransportError(_) | JsonRpcClientError::JsonError(_))
+    )
+}
+
+/// A contract deployed on Starknet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeployedContract {
+    pub address: FieldElement,
+    pub class_hash: FieldElement,
+    /// Hash of the deploy transaction, if known.
+    pub transaction_hash: Option<FieldElement>,
+}
+
+/// Header of a block accepted on L2 or L1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockHeader {
+    pub number: u64,
+    pub hash: FieldElement,
+    pub parent_hash: FieldElement,
+    pub timestamp: u64,
+}
+
 /// Starknet client interface with required methods
 /// for arkproject capabilities only.
 #[cfg_attr(any(test, feature = "mock"), automock)]
 #[async_trait]
 pub trait StarknetClient {
-    ///
+    /// Creates a new client connected to the given RPC url.
     fn new(rpc_url: &str) -> Result<Self, StarknetClientError>
     where
         Self: Sized;
 
-    ///
+    /// Returns the events of the given transaction receipt, filtered by keys.
+    /// If `keys` is `None`, all the events are returned.
     async fn events_from_tx_receipt(
         &self,
         transaction_hash: FieldElement,
         keys: Option<Vec<Vec<FieldElement>>>,
     ) -> Result<Vec<EmittedEvent>, StarknetClientError>;
 
-    ///
+    /// Returns the block timestamp and the hashes of its transactions.
     async fn block_txs_hashes(
         &self,
         block: BlockId,
     ) -> Result<(u64, Vec<FieldElement>), StarknetClientError>;
 
-    ///
+    /// Converts a `BlockId` into a block number.
     async fn block_id_to_u64(&self, id: &BlockId) -> Result<u64, StarknetClientError>;
 
-    ///
+    /// Parses a block range from two strings (number, hash, `latest` or `pending`).
     fn parse_block_range(
         &self,
         from: &str,
         to: &str,
     ) -> Result<(BlockId, BlockId), StarknetClientError>;
 
-    ///
+    /// Parses a block id from a string (number, hash, `latest` or `pending`).
     fn parse_block_id(&self, id: &str) -> Result<BlockId, StarknetClientError>;
 
-    ///
+    /// Returns the contracts deployed in the given block, from its state update.
+    /// This includes contracts deployed by any means (syscall, UDC, `DEPLOY_ACCOUNT`...),
+    /// but without the transaction hash.
+    async fn deployed_contracts(
+        &self,
+        block: BlockId,
+    ) -> Result<Vec<DeployedContract>, StarknetClientError>;
+
+    /// Returns the contracts deployed by the `DEPLOY` and `DEPLOY_ACCOUNT`
+    /// transactions of the given block, from their receipts.
+    async fn deploy_transactions(
+        &self,
+        block: BlockId,
+    ) -> Result<Vec<DeployedContract>, StarknetClientError>;
+
+    /// Returns the class hash of the contract at the given address.
+    async fn class_hash_at(
+        &self,
+        contract_address: FieldElement,
+        block: BlockId,
+    ) -> Result<FieldElement, StarknetClientError>;
+
+    /// Returns the timestamp of the given block.
     async fn block_time(&self, block: BlockId) -> Result<u64, StarknetClientError>;
 
-    ///
+    /// Returns the header of the given block, which must not be the pending one.
+    async fn block_header(&self, block: BlockId) -> Result<BlockHeader, StarknetClientError>;
+
+    /// Returns the latest block number.
     async fn block_number(&self) -> Result<u64, StarknetClientError>;
 
     /// On Starknet, a chunk size limits the maximum number of events
@@ -102,3 +194,32 @@ pub trait StarknetClient {
         block: BlockId,
     ) -> Result<Vec<FieldElement>, StarknetClientError>;
 }
+
+/// Calls a contract trying all the given selectors in order, until one
+/// of them is found in the contract. All the selectors must accept the
+/// same arguments.
+/// Returns `EntrypointNotFound` if none of the selectors exists.
+pub async fn call_contract_selectors<C: StarknetClient + Sync + ?Sized>(
+    client: &C,
+    contract_address: FieldElement,
+    selectors: &[FieldElement],
+    calldata: Vec<FieldElement>,
+    block: BlockId,
+) -> Result<Vec<FieldElement>, StarknetClientError> {
+    let mut last_error = StarknetClientError::EntrypointNotFound(format!(
+        "no selector found in contract {:#x}",
+        contract_address
+    ));
+
+    for selector in selectors {
+        match client
+            .call_contract(contract_address, *selector, calldata.clone(), block)
+            .await
+        {
+            Err(e @ StarknetClientError::EntrypointNotFound(_)) => last_error = e,
+            r => return r,
+        }
+    }
+
+    Err(last_error)
+}
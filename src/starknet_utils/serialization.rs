@@ -0,0 +1,424 @@
+//! Serde support for `CairoU256`, `ByteArray` and hexadecimal felts.
+//!
+//! By default, `CairoU256` is serialized as a `0x` prefixed hexadecimal string,
+//! and `ByteArray` as a string, or as its felts if it is not valid UTF-8.
+//! Other wire forms can be selected on a field with `#[serde(with = "...")]`
+//! using the modules below.
+//!
+//! Deserialization always accepts every wire form of the type, whatever the
+//! module used.
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use starknet::core::types::FieldElement;
+use std::fmt;
+
+use super::byte_array::ByteArray;
+use super::CairoU256;
+
+impl Serialize for CairoU256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        u256_hex::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CairoU256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(CairoU256Visitor)
+    }
+}
+
+/// A `ByteArray` is not bound to an encoding: the bytes of an invalid UTF-8
+/// string are kept by falling back to the `byte_array_felts` form, which is
+/// deserialized back to the same `ByteArray`.
+impl Serialize for ByteArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.to_string() {
+            Ok(s) => serializer.serialize_str(&s),
+            Err(_) => byte_array_felts::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteArray {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ByteArrayVisitor)
+    }
+}
+
+/// `CairoU256` as a `0x` prefixed hexadecimal string, padded to 64 digits.
+pub mod u256_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &CairoU256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CairoU256, D::Error> {
+        CairoU256::deserialize(deserializer)
+    }
+}
+
+/// `CairoU256` as a decimal string, not padded.
+pub mod u256_decimal {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &CairoU256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_decimal(false))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CairoU256, D::Error> {
+        CairoU256::deserialize(deserializer)
+    }
+}
+
+/// `CairoU256` as `{ "low": "0x..", "high": "0x.." }`, the way it is
+/// represented in Cairo.
+pub mod u256_low_high {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &CairoU256, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("CairoU256", 2)?;
+        s.serialize_field("low", &format!("{:#x}", value.low))?;
+        s.serialize_field("high", &format!("{:#x}", value.high))?;
+        s.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CairoU256, D::Error> {
+        CairoU256::deserialize(deserializer)
+    }
+}
+
+/// `ByteArray` as a string. Fails to serialize if the `ByteArray`
+/// is not a valid UTF-8 string.
+pub mod byte_array_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &ByteArray, serializer: S) -> Result<S::Ok, S::Error> {
+        let s = value.to_string().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ByteArray, D::Error> {
+        ByteArray::deserialize(deserializer)
+    }
+}
+
+/// `ByteArray` as its Cairo serialized felts
+/// `[data_len, data..., pending_word, pending_word_len]`, each felt
+/// being a `0x` prefixed hexadecimal string.
+pub mod byte_array_felts {
+    use super::*;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<S: Serializer>(value: &ByteArray, serializer: S) -> Result<S::Ok, S::Error> {
+        let felts = value.to_felts();
+
+        let mut seq = serializer.serialize_seq(Some(felts.len()))?;
+        for felt in felts {
+            seq.serialize_element(&format!("{:#x}", felt))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ByteArray, D::Error> {
+        ByteArray::deserialize(deserializer)
+    }
+}
+
+/// `FieldElement` as a `0x` prefixed hexadecimal string, not padded,
+/// instead of the decimal string of its default implementation.
+pub mod felt_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &FieldElement,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<FieldElement, D::Error> {
+        FieldElement::deserialize(deserializer)
+    }
+}
+
+/// A `u128` deserialized from a number or a hexadecimal / decimal string.
+struct U128Wire(u128);
+
+impl<'de> Deserialize<'de> for U128Wire {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(U128Visitor)
+    }
+}
+
+struct U128Visitor;
+
+impl<'de> Visitor<'de> for U128Visitor {
+    type Value = U128Wire;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an integer or a hexadecimal or decimal string")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(U128Wire(v.into()))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(U128Wire(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v.strip_prefix("0x") {
+            Some(hex) => u128::from_str_radix(hex, 16),
+            None => v.parse::<u128>(),
+        }
+        .map(U128Wire)
+        .map_err(|_| E::custom(format!("invalid u128 string: {}", v)))
+    }
+}
+
+struct CairoU256Visitor;
+
+impl<'de> Visitor<'de> for CairoU256Visitor {
+    type Value = CairoU256;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a hexadecimal or decimal string, an integer or a {{low, high}} map"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse::<CairoU256>().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(CairoU256 {
+            low: v.into(),
+            high: 0,
+        })
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(CairoU256 { low: v, high: 0 })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut low = None;
+        let mut high = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "low" => low = Some(map.next_value::<U128Wire>()?.0),
+                "high" => high = Some(map.next_value::<U128Wire>()?.0),
+                _ => return Err(de::Error::unknown_field(&key, &["low", "high"])),
+            }
+        }
+
+        Ok(CairoU256 {
+            low: low.ok_or_else(|| de::Error::missing_field("low"))?,
+            high: high.ok_or_else(|| de::Error::missing_field("high"))?,
+        })
+    }
+}
+
+struct ByteArrayVisitor;
+
+impl<'de> Visitor<'de> for ByteArrayVisitor {
+    type Value = ByteArray;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a string or a sequence of felts")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(ByteArray::from_string(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut felts = Vec::new();
+        while let Some(felt) = seq.next_element::<FieldElement>()? {
+            felts.push(felt);
+        }
+
+        let (byte_array, consumed) = ByteArray::from_felts(&felts).map_err(de::Error::custom)?;
+
+        if consumed != felts.len() {
+            return Err(de::Error::invalid_length(felts.len(), &self));
+        }
+
+        Ok(byte_array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_array_utf8_as_string() {
+        let byte_array = ByteArray::from_string("Token #1 ✓");
+
+        let json = serde_json::to_string(&byte_array).unwrap();
+
+        assert_eq!(json, r#""Token #1 ✓""#);
+        assert_eq!(
+            serde_json::from_str::<ByteArray>(&json).unwrap(),
+            byte_array
+        );
+    }
+
+    #[test]
+    fn byte_array_invalid_utf8_as_felts() {
+        let byte_array = ByteArray::from_bytes(&[0x41, 0xff, 0x42]);
+
+        let json = serde_json::to_string(&byte_array).unwrap();
+
+        assert_eq!(json, r#"["0x0","0x41ff42","0x3"]"#);
+        assert_eq!(
+            serde_json::from_str::<ByteArray>(&json).unwrap(),
+            byte_array
+        );
+        assert!(serde_json::to_string(&Wrapper(byte_array)).is_err());
+    }
+
+    /// Forces the string form.
+    #[derive(Serialize)]
+    struct Wrapper(#[serde(with = "byte_array_string")] ByteArray);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct U256Forms {
+        default: CairoU256,
+        #[serde(with = "u256_hex")]
+        hex: CairoU256,
+        #[serde(with = "u256_decimal")]
+        decimal: CairoU256,
+        #[serde(with = "u256_low_high")]
+        low_high: CairoU256,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OtherForms {
+        #[serde(with = "byte_array_string")]
+        string: ByteArray,
+        #[serde(with = "byte_array_felts")]
+        felts: ByteArray,
+        #[serde(with = "felt_hex")]
+        felt: FieldElement,
+    }
+
+    fn u256(json: &str) -> Result<CairoU256, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn u256_forms_round_trip() {
+        let value = CairoU256::new(0x10, 0x1);
+        let forms = U256Forms {
+            default: value,
+            hex: value,
+            decimal: value,
+            low_high: value,
+        };
+
+        let json = serde_json::to_value(&forms).unwrap();
+
+        let padded = format!("0x{:0>64}", "100000000000000000000000000000010");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "default": padded,
+                "hex": padded,
+                "decimal": "340282366920938463463374607431768211472",
+                "low_high": { "low": "0x10", "high": "0x1" },
+            })
+        );
+        assert_eq!(serde_json::from_value::<U256Forms>(json).unwrap(), forms);
+
+        let max = U256Forms {
+            default: CairoU256::MAX,
+            hex: CairoU256::MAX,
+            decimal: CairoU256::MAX,
+            low_high: CairoU256::MAX,
+        };
+        let json = serde_json::to_string(&max).unwrap();
+        assert_eq!(serde_json::from_str::<U256Forms>(&json).unwrap(), max);
+    }
+
+    #[test]
+    fn u256_accepts_every_form() {
+        let value = CairoU256::from(255_u64);
+
+        for json in [
+            r#""0xff""#,
+            r#""255""#,
+            "255",
+            r#"{"low": "0xff", "high": "0x0"}"#,
+            r#"{"high": 0, "low": "255"}"#,
+            r#"{"low": 255, "high": 0}"#,
+        ] {
+            assert_eq!(u256(json).unwrap(), value, "{}", json);
+        }
+
+        // The form of a field doesn't restrict what it accepts.
+        let forms: U256Forms = serde_json::from_str(
+            r#"{"default": 1, "hex": "1", "decimal": "0x1", "low_high": "0x1"}"#,
+        )
+        .unwrap();
+        assert!([forms.default, forms.hex, forms.decimal, forms.low_high]
+            .iter()
+            .all(|v| *v == CairoU256::ONE));
+    }
+
+    #[test]
+    fn u256_invalid_forms() {
+        let error = |json: &str| u256(json).unwrap_err().to_string();
+
+        assert!(error(r#"{"low": "0x1"}"#).contains("missing field `high`"));
+        assert!(error(r#"{"high": "0x1"}"#).contains("missing field `low`"));
+        assert!(error(r#"{"low": 1, "high": 0, "mid": 0}"#).contains("unknown field `mid`"));
+        // A limb above u128::MAX.
+        assert!(
+            error(&format!(r#"{{"low": "0x1{}", "high": 0}}"#, "0".repeat(32)))
+                .contains("invalid u128 string")
+        );
+        assert!(error(r#"{"low": "low", "high": 0}"#).contains("invalid u128 string"));
+        assert!(error(r#"{"low": -1, "high": 0}"#).contains("an integer or a hexadecimal"));
+
+        assert!(u256(&format!(r#""0x1{}""#, "0".repeat(64))).is_err());
+        assert!(u256(r#""""#).is_err());
+        assert!(u256("-1").is_err());
+        assert!(u256("1.5").is_err());
+        assert!(u256("true").is_err());
+    }
+
+    #[test]
+    fn other_forms_round_trip() {
+        let forms = OtherForms {
+            string: ByteArray::from_string("Token"),
+            felts: ByteArray::from_string("Token"),
+            felt: FieldElement::from(0xabc_u64),
+        };
+
+        let json = serde_json::to_value(&forms).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "string": "Token",
+                "felts": ["0x0", "0x546f6b656e", "0x5"],
+                "felt": "0xabc",
+            })
+        );
+        assert_eq!(serde_json::from_value::<OtherForms>(json).unwrap(), forms);
+
+        // Felts with trailing elements, or of an invalid `ByteArray`.
+        assert!(serde_json::from_str::<ByteArray>(r#"["0x0", "0x41", "0x1", "0x0"]"#).is_err());
+        assert!(serde_json::from_str::<ByteArray>(r#"["0x0", "0x41"]"#).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! State of the block watcher following the pending block, shared with
+//! the health endpoints.
+use serde::Serialize;
+use starknet::core::types::FieldElement;
+use std::collections::BTreeMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of processed blocks whose hash is kept to detect reorganizations.
+pub const MAX_REORG_DEPTH: usize = 64;
+
+/// Progress of the watcher, timestamps in seconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WatcherState {
+    /// Timestamp of the pending block being followed. When a pending block with
+    /// another timestamp appears, the blocks up to the chain head are processed.
+    pub pending_timestamp: Option<u64>,
+    pub pending_transactions: usize,
+    /// Latest block number of the chain, when last fetched.
+    pub chain_head: Option<u64>,
+    pub last_processed_block: Option<u64>,
+    pub last_processed_timestamp: Option<u64>,
+    pub last_rpc_success: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct BlockWatcher {
+    started_at: u64,
+    state: RwLock<WatcherState>,
+}
+
+impl Default for BlockWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockWatcher {
+    pub fn new() -> Self {
+        Self {
+            started_at: unix_now(),
+            state: RwLock::new(WatcherState::default()),
+        }
+    }
+
+    pub fn state(&self) -> WatcherState {
+        *self.read()
+    }
+
+    /// Records a poll of the pending block, and returns the timestamp of the
+    /// pending block followed, initialized with the first one polled.
+    pub fn pending_polled(&self, timestamp: u64, transactions: usize) -> u64 {
+        let mut state = self.write();
+        state.pending_transactions = transactions;
+        state.last_rpc_success = Some(unix_now());
+        *state.pending_timestamp.get_or_insert(timestamp)
+    }
+
+    /// Records the latest block number of the chain. A node lagging behind may
+    /// return a block older than the last processed one.
+    pub fn chain_head_fetched(&self, block_number: u64) {
+        let mut state = self.write();
+        state.chain_head = Some(block_number);
+        state.last_rpc_success = Some(unix_now());
+    }
+
+    /// Records the processing of a block.
+    pub fn block_processed(&self, block_number: u64, timestamp: u64) {
+        let mut state = self.write();
+        state.last_processed_block = Some(block_number);
+        state.last_processed_timestamp = Some(timestamp);
+        state.last_rpc_success = Some(unix_now());
+    }
+
+    /// Records a block replaced by a reorganization, to be processed again.
+    pub fn block_replaced(&self, block_number: u64) {
+        let mut state = self.write();
+        state.last_processed_block = block_number.checked_sub(1);
+    }
+
+    /// Follows a new pending block, once the blocks up to the chain head are processed.
+    pub fn pending_followed(&self, timestamp: u64) {
+        self.write().pending_timestamp = Some(timestamp);
+    }
+
+    /// Blocks between the chain head and the last processed block.
+    pub fn lag_blocks(&self) -> u64 {
+        let state = self.read();
+        match (state.chain_head, state.last_processed_block) {
+            (Some(head), Some(last)) => head.saturating_sub(last),
+            _ => 0,
+        }
+    }
+
+    /// Seconds since the timestamp of the last processed block, or since
+    /// the watcher started if no block was processed yet.
+    pub fn lag_seconds(&self) -> u64 {
+        let since = self
+            .read()
+            .last_processed_timestamp
+            .unwrap_or(self.started_at);
+        unix_now().saturating_sub(since)
+    }
+
+    /// Seconds since the last successful RPC call, `None` if none succeeded yet.
+    pub fn rpc_age_seconds(&self) -> Option<u64> {
+        let since = self.read().last_rpc_success?;
+        Some(unix_now().saturating_sub(since))
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, WatcherState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, WatcherState> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Hashes of the last processed blocks. A block whose parent hash is not the
+/// hash of the processed block before it comes from a reorganized chain.
+#[derive(Debug, Clone)]
+pub struct RecentBlocks {
+    hashes: BTreeMap<u64, FieldElement>,
+    capacity: usize,
+}
+
+impl Default for RecentBlocks {
+    fn default() -> Self {
+        Self::new(MAX_REORG_DEPTH)
+    }
+}
+
+impl RecentBlocks {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            hashes: BTreeMap::new(),
+            capacity,
+        }
+    }
+
+    /// Returns `false` if the block before `block_number` was processed with
+    /// another hash than `parent_hash`. Unknown parents are accepted.
+    pub fn is_child(&self, block_number: u64, parent_hash: FieldElement) -> bool {
+        block_number
+            .checked_sub(1)
+            .and_then(|parent| self.hashes.get(&parent))
+            .is_none_or(|hash| *hash == parent_hash)
+    }
+
+    /// Records a processed block, keeping the hashes of the last `capacity` blocks.
+    pub fn push(&mut self, block_number: u64, hash: FieldElement) {
+        self.hashes.insert(block_number, hash);
+        while self.hashes.len() > self.capacity {
+            self.hashes.pop_first();
+        }
+    }
+
+    /// Forgets a block replaced by a reorganization, and the blocks after it.
+    pub fn remove_from(&mut self, block_number: u64) {
+        self.hashes.split_off(&block_number);
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::macros::felt;
+
+    #[test]
+    fn recent_blocks_detect_replaced_parents() {
+        let mut blocks = RecentBlocks::new(2);
+        assert!(blocks.is_child(10, felt!("0x9")));
+
+        blocks.push(10, felt!("0xa"));
+        blocks.push(11, felt!("0xb"));
+        assert!(blocks.is_child(12, felt!("0xb")));
+        assert!(!blocks.is_child(12, felt!("0xbb")));
+        assert!(!blocks.is_child(11, felt!("0xaa")));
+
+        // Only the hashes of the last two blocks are kept.
+        blocks.push(12, felt!("0xc"));
+        assert!(blocks.is_child(11, felt!("0xaa")));
+
+        blocks.remove_from(12);
+        assert!(blocks.is_child(12, felt!("0xb")));
+        assert!(blocks.is_child(13, felt!("0xcc")));
+    }
+}